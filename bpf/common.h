@@ -14,8 +14,15 @@ struct TaskStats {
     __u64 page_faults;      /* user faults (count) */
     __u64 total_oncpu_us;   /* accumulated on-CPU time (usec) */
     __u32 last_cpu;         /* last CPU seen */
+    __u32 _pad;
+    __u64 nr_bursts;        /* completed on-CPU stretches */
+    __u64 max_burst_us;     /* longest single on-CPU stretch (usec) */
 };
 
+/* tuner_event.kind */
+#define EVT_FUTEX_SPIKE 1
+#define EVT_PROC_EXIT   2   /* a target tgid exited; val_us unused */
+
 struct tuner_event {
     __u32 pid;     /* TGID (userspace stores as pid) */
     __u32 kind;    /* event kind */
@@ -69,4 +76,22 @@ static __always_inline void emit_evt(__u32 tgid, __u32 kind, __u64 val_us)
     e->val_us = val_us;
     e->ts_ns = bpf_ktime_get_ns();
     bpf_ringbuf_submit(e, 0);
-}
\ No newline at end of file
+}
+/* log2 histogram layout, shared by LAT_HIST (tuner) and NET_HIST (sockops) */
+#define HIST_SLOTS 32
+
+struct lat_hist {
+    __u64 slots[HIST_SLOTS]; /* slot i counts values in [2^i, 2^(i+1)); slot 0 also holds 0 */
+};
+
+static __always_inline __u32 log2_u64(__u64 v)
+{
+    __u32 r = 0;
+    if (v >> 32) { v >>= 32; r += 32; }
+    if (v >> 16) { v >>= 16; r += 16; }
+    if (v >> 8)  { v >>= 8;  r += 8; }
+    if (v >> 4)  { v >>= 4;  r += 4; }
+    if (v >> 2)  { v >>= 2;  r += 2; }
+    if (v >> 1)  { r += 1; }
+    return r;
+}
@@ -23,34 +23,81 @@ struct {
 } PREFETCH_EVENTS SEC(".maps");
 
 
-SEC("fentry/filemap_fault") int BPF_PROG(on_filemap_fault_fentry, struct vm_fault *vmf) {
-    __u64 pidtgid = bpf_get_current_pid_tgid();
-    __u32 tgid = pidtgid >> 32;
-    if (!is_target_tgid(tgid)) return 0;
-
-    struct file *f = BPF_CORE_READ(vmf, vma, vm_file);
-    if (!f) return 0;
+/* Set from userspace after load; zeroed = emit every fault, no rate limit. */
+struct prefetch_cfg {
+    __u32 sample_shift;   /* emit 1 of every 2^shift target faults per CPU */
+    __u32 max_per_sec;    /* per-CPU event budget per second, 0 = unlimited */
+};
 
-    struct inode *inode = BPF_CORE_READ(f, f_inode);
-    if (!inode) return 0;
+struct {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, 1);
+    __type(key, __u32);
+    __type(value, struct prefetch_cfg);
+} PREFETCH_CFG SEC(".maps");
+
+struct rl_state {
+    __u64 win_start_ns;
+    __u64 count;
+    __u64 seq;
+};
 
-    struct prefetch_evt *e = bpf_ringbuf_reserve(&PREFETCH_EVENTS, sizeof(*e), 0);
-    if (!e) return 0;
+struct {
+    __uint(type, BPF_MAP_TYPE_PERCPU_ARRAY);
+    __uint(max_entries, 1);
+    __type(key, __u32);
+    __type(value, struct rl_state);
+} PREFETCH_RL SEC(".maps");
+
+/* PREFETCH_STATS slots */
+#define PF_SEEN         0
+#define PF_SAMPLED_OUT  1
+#define PF_RATE_LIMITED 2
+#define PF_RB_FULL      3
+#define PF_NR_STATS     4
 
-    e->tgid  = tgid;
-    e->pid   = (__u32)pidtgid;
-    e->ts_ns = bpf_ktime_get_ns();
-    e->ino   = BPF_CORE_READ(inode, i_ino);
-    e->sb_dev= (__u64)BPF_CORE_READ(inode, i_sb, s_dev);
-    e->pgoff = BPF_CORE_READ(vmf, pgoff);
+struct {
+    __uint(type, BPF_MAP_TYPE_PERCPU_ARRAY);
+    __uint(max_entries, PF_NR_STATS);
+    __type(key, __u32);
+    __type(value, __u64);
+} PREFETCH_STATS SEC(".maps");
 
-    bpf_ringbuf_submit(e, 0);
-    return 0;
+static __always_inline void pf_count(__u32 slot)
+{
+    __u64 *v = bpf_map_lookup_elem(&PREFETCH_STATS, &slot);
+    if (v) (*v)++;
 }
 
+/* Sampling and a fixed one-second window, both per CPU, so fault storms
+ * cannot overflow PREFETCH_EVENTS. Returns 1 when the event may be emitted. */
+static __always_inline int pf_admit(__u64 now)
+{
+    __u32 zero = 0;
+    struct prefetch_cfg *cfg = bpf_map_lookup_elem(&PREFETCH_CFG, &zero);
+    struct rl_state *rl = bpf_map_lookup_elem(&PREFETCH_RL, &zero);
+    if (!cfg || !rl) return 1;
+
+    __u32 shift = cfg->sample_shift & 31;
+    if (shift && (rl->seq++ & ((1ULL << shift) - 1))) {
+        pf_count(PF_SAMPLED_OUT);
+        return 0;
+    }
+    if (cfg->max_per_sec) {
+        if (now - rl->win_start_ns >= 1000000000ULL) {
+            rl->win_start_ns = now;
+            rl->count = 0;
+        }
+        if (rl->count >= cfg->max_per_sec) {
+            pf_count(PF_RATE_LIMITED);
+            return 0;
+        }
+        rl->count++;
+    }
+    return 1;
+}
 
-SEC("kprobe/filemap_fault")
-int BPF_KPROBE(on_filemap_fault, struct vm_fault *vmf)
+static __always_inline int handle_fault(struct vm_fault *vmf)
 {
     __u64 pidtgid = bpf_get_current_pid_tgid();
     __u32 tgid = pidtgid >> 32;
@@ -62,16 +109,36 @@ int BPF_KPROBE(on_filemap_fault, struct vm_fault *vmf)
     struct inode *inode = BPF_CORE_READ(f, f_inode);
     if (!inode) return 0;
 
+    pf_count(PF_SEEN);
+    __u64 now = bpf_ktime_get_ns();
+    if (!pf_admit(now)) return 0;
+
     struct prefetch_evt *e = bpf_ringbuf_reserve(&PREFETCH_EVENTS, sizeof(*e), 0);
-    if (!e) return 0;
+    if (!e) {
+        pf_count(PF_RB_FULL);
+        return 0;
+    }
 
     e->tgid  = tgid;
     e->pid   = (__u32)pidtgid;
-    e->ts_ns = bpf_ktime_get_ns();
+    e->ts_ns = now;
     e->ino   = BPF_CORE_READ(inode, i_ino);
     e->sb_dev= (__u64)BPF_CORE_READ(inode, i_sb, s_dev);
     e->pgoff = BPF_CORE_READ(vmf, pgoff);
 
     bpf_ringbuf_submit(e, 0);
     return 0;
-}
\ No newline at end of file
+}
+
+/* Userspace loads exactly one of these two; fentry is preferred. */
+SEC("fentry/filemap_fault")
+int BPF_PROG(on_filemap_fault_fentry, struct vm_fault *vmf)
+{
+    return handle_fault(vmf);
+}
+
+SEC("kprobe/filemap_fault")
+int BPF_KPROBE(on_filemap_fault, struct vm_fault *vmf)
+{
+    return handle_fault(vmf);
+}
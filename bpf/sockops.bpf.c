@@ -24,27 +24,242 @@
 #ifndef TCP_KEEPCNT
 #define TCP_KEEPCNT 6
 #endif
+#ifndef SO_SNDBUF
+#define SO_SNDBUF 7
+#endif
+#ifndef SO_RCVBUF
+#define SO_RCVBUF 8
+#endif
+#ifndef TCP_NOTSENT_LOWAT
+#define TCP_NOTSENT_LOWAT 25
+#endif
+#ifndef TCP_BPF_IW
+#define TCP_BPF_IW 1001
+#endif
 
 char LICENSE[] SEC("license") = "GPL";
 
+extern int CONFIG_HZ __kconfig __weak;
+
+/* sock_rule.flags: which knobs the rule sets. Must match src/sockops.rs */
+#define SR_ENABLED   (1 << 0)
+#define SR_CC        (1 << 1)
+#define SR_KEEPALIVE (1 << 2)
+#define SR_NOTSENT   (1 << 3)
+#define SR_SNDBUF    (1 << 4)
+#define SR_RCVBUF    (1 << 5)
+#define SR_IW        (1 << 6)
+#define SR_RTO       (1 << 7)
+
+#define SOCK_RULES  8
+#define CC_NAME_MAX 16
+
+struct sock_rule {
+    __u32 flags;
+    __u16 local_port;    /* 0 = any */
+    __u16 remote_port;   /* 0 = any */
+    char  cc[CC_NAME_MAX];
+    __u32 keep_idle;
+    __u32 keep_intvl;
+    __u32 keep_cnt;
+    __u32 notsent_lowat;
+    __u32 sndbuf;
+    __u32 rcvbuf;
+    __u32 init_cwnd;
+    __u32 rto_init_ms;
+};
+
+/* Rules in priority order, written from userspace; the first enabled match wins. */
+struct {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, SOCK_RULES);
+    __type(key, __u32);
+    __type(value, struct sock_rule);
+} SOCK_RULES_MAP SEC(".maps");
+
+/* Telemetry for sockets of the attached (target) cgroup. Read by src/bpf.rs. */
+struct net_stats {
+    __u64 rtt_samples;
+    __u64 srtt_us_sum;
+    __u64 retrans;
+    __u64 opened_active;
+    __u64 opened_passive;
+    __u64 closed;
+    __u64 connects;
+    __u64 connect_us_sum;
+};
+
+struct {
+    __uint(type, BPF_MAP_TYPE_PERCPU_ARRAY);
+    __uint(max_entries, 1);
+    __type(key, __u32);
+    __type(value, struct net_stats);
+} NET_STATS SEC(".maps");
+
+/* NET_HIST slots */
+#define NET_HIST_RTT     0
+#define NET_HIST_CONNECT 1
+
+struct {
+    __uint(type, BPF_MAP_TYPE_PERCPU_ARRAY);
+    __uint(max_entries, 2);
+    __type(key, __u32);
+    __type(value, struct lat_hist);
+} NET_HIST SEC(".maps");
+
+/* connect() start per socket, for establishment latency */
+struct {
+    __uint(type, BPF_MAP_TYPE_SK_STORAGE);
+    __uint(map_flags, BPF_F_NO_PREALLOC);
+    __type(key, int);
+    __type(value, __u64);
+} CONNECT_TS SEC(".maps");
+
+static __always_inline struct net_stats *net_stats(void)
+{
+    __u32 zero = 0;
+    return bpf_map_lookup_elem(&NET_STATS, &zero);
+}
+
+static __always_inline void net_hist_add(__u32 idx, __u64 val_us)
+{
+    struct lat_hist *h = bpf_map_lookup_elem(&NET_HIST, &idx);
+    if (!h) return;
+    __u32 slot = log2_u64(val_us);
+    if (slot >= HIST_SLOTS) slot = HIST_SLOTS - 1;
+    h->slots[slot]++;
+}
+
+static __always_inline void on_established(struct bpf_sock_ops *skops, int active)
+{
+    struct net_stats *st = net_stats();
+    if (!st) return;
+    if (active) {
+        st->opened_active++;
+        struct bpf_sock *sk = skops->sk;
+        if (sk) {
+            __u64 *ts = bpf_sk_storage_get(&CONNECT_TS, sk, 0, 0);
+            if (ts && *ts) {
+                __u64 us = (bpf_ktime_get_ns() - *ts) / 1000;
+                st->connects++;
+                st->connect_us_sum += us;
+                net_hist_add(NET_HIST_CONNECT, us);
+            }
+        }
+    } else {
+        st->opened_passive++;
+    }
+    bpf_sock_ops_cb_flags_set(skops, BPF_SOCK_OPS_RTT_CB_FLAG |
+                                     BPF_SOCK_OPS_RETRANS_CB_FLAG |
+                                     BPF_SOCK_OPS_STATE_CB_FLAG);
+}
+
+static __always_inline struct sock_rule *match_rule(struct bpf_sock_ops *skops)
+{
+    __u16 lport = (__u16)skops->local_port;
+    __u16 rport = (__u16)bpf_ntohl(skops->remote_port);
+    for (__u32 i = 0; i < SOCK_RULES; i++) {
+        __u32 k = i;
+        struct sock_rule *r = bpf_map_lookup_elem(&SOCK_RULES_MAP, &k);
+        if (!r || !(r->flags & SR_ENABLED))
+            continue;
+        if (r->local_port && r->local_port != lport)
+            continue;
+        if (r->remote_port && r->remote_port != rport)
+            continue;
+        return r;
+    }
+    return 0;
+}
+
+static __always_inline void apply_rule(struct bpf_sock_ops *skops, struct sock_rule *r)
+{
+    if (r->flags & SR_CC) {
+        char cc[CC_NAME_MAX];
+        __builtin_memcpy(cc, r->cc, sizeof(cc));
+        cc[CC_NAME_MAX - 1] = 0;
+        bpf_setsockopt(skops, SOL_TCP, TCP_CONGESTION, cc, sizeof(cc));
+    }
+    if (r->flags & SR_KEEPALIVE) {
+        int one = 1;
+        int idle = r->keep_idle, intvl = r->keep_intvl, cnt = r->keep_cnt;
+        bpf_setsockopt(skops, SOL_SOCKET, SO_KEEPALIVE, &one, sizeof(one));
+        if (idle) bpf_setsockopt(skops, SOL_TCP, TCP_KEEPIDLE, &idle, sizeof(idle));
+        if (intvl) bpf_setsockopt(skops, SOL_TCP, TCP_KEEPINTVL, &intvl, sizeof(intvl));
+        if (cnt) bpf_setsockopt(skops, SOL_TCP, TCP_KEEPCNT, &cnt, sizeof(cnt));
+    }
+    if (r->flags & SR_NOTSENT) {
+        int v = r->notsent_lowat;
+        bpf_setsockopt(skops, SOL_TCP, TCP_NOTSENT_LOWAT, &v, sizeof(v));
+    }
+    if (r->flags & SR_SNDBUF) {
+        int v = r->sndbuf;
+        bpf_setsockopt(skops, SOL_SOCKET, SO_SNDBUF, &v, sizeof(v));
+    }
+    if (r->flags & SR_RCVBUF) {
+        int v = r->rcvbuf;
+        bpf_setsockopt(skops, SOL_SOCKET, SO_RCVBUF, &v, sizeof(v));
+    }
+}
+
 SEC("sockops")
 int sockops_prog(struct bpf_sock_ops *skops)
 {
     int op = (int)skops->op;
-    if (op != BPF_SOCK_OPS_TCP_CONNECT_CB &&
-        op != BPF_SOCK_OPS_ACTIVE_ESTABLISHED_CB &&
-        op != BPF_SOCK_OPS_PASSIVE_ESTABLISHED_CB)
-        return 0;
-
-    // Try to set BBR
-    char cong[] = "bbr";
-    bpf_setsockopt(skops, SOL_TCP, TCP_CONGESTION, cong, sizeof(cong)-1);
-
-    // Enable keepalive + tune timings
-    int one = 1, idle = 30, intvl = 10, cnt = 6;
-    bpf_setsockopt(skops, SOL_SOCKET, SO_KEEPALIVE, &one, sizeof(one));
-    bpf_setsockopt(skops, SOL_TCP, TCP_KEEPIDLE, &idle, sizeof(idle));
-    bpf_setsockopt(skops, SOL_TCP, TCP_KEEPINTVL, &intvl, sizeof(intvl));
-    bpf_setsockopt(skops, SOL_TCP, TCP_KEEPCNT, &cnt, sizeof(cnt));
-    return 0;
-}
\ No newline at end of file
+    struct sock_rule *r;
+
+    switch (op) {
+    case BPF_SOCK_OPS_TIMEOUT_INIT:
+        /* reply is the initial RTO in jiffies; -1 keeps the kernel default */
+        r = match_rule(skops);
+        if (r && (r->flags & SR_RTO) && CONFIG_HZ)
+            skops->reply = (r->rto_init_ms * CONFIG_HZ) / 1000;
+        else
+            skops->reply = -1;
+        return 1;
+    case BPF_SOCK_OPS_TCP_CONNECT_CB: {
+        struct bpf_sock *sk = skops->sk;
+        if (sk) {
+            __u64 *ts = bpf_sk_storage_get(&CONNECT_TS, sk, 0, BPF_SK_STORAGE_GET_F_CREATE);
+            if (ts) *ts = bpf_ktime_get_ns();
+        }
+        r = match_rule(skops);
+        if (r) apply_rule(skops, r);
+        return 1;
+    }
+    case BPF_SOCK_OPS_ACTIVE_ESTABLISHED_CB:
+    case BPF_SOCK_OPS_PASSIVE_ESTABLISHED_CB:
+        on_established(skops, op == BPF_SOCK_OPS_ACTIVE_ESTABLISHED_CB);
+        r = match_rule(skops);
+        if (!r) return 1;
+        apply_rule(skops, r);
+        if (r->flags & SR_IW) {
+            int iw = r->init_cwnd;
+            bpf_setsockopt(skops, SOL_TCP, TCP_BPF_IW, &iw, sizeof(iw));
+        }
+        return 1;
+    case BPF_SOCK_OPS_RTT_CB: {
+        struct net_stats *st = net_stats();
+        if (!st) return 1;
+        __u64 srtt = skops->srtt_us >> 3;   /* kernel keeps srtt << 3 */
+        st->rtt_samples++;
+        st->srtt_us_sum += srtt;
+        net_hist_add(NET_HIST_RTT, srtt);
+        return 1;
+    }
+    case BPF_SOCK_OPS_RETRANS_CB: {
+        struct net_stats *st = net_stats();
+        if (st) st->retrans++;
+        return 1;
+    }
+    case BPF_SOCK_OPS_STATE_CB: {
+        /* args[0] = old state, args[1] = new state */
+        if (skops->args[1] == BPF_TCP_CLOSE) {
+            struct net_stats *st = net_stats();
+            if (st) st->closed++;
+        }
+        return 1;
+    }
+    }
+    return 1;
+}
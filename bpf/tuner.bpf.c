@@ -44,6 +44,50 @@ struct {
     __type(value, __u64); // ts_ns
 } TID_WAKE_TS SEC(".maps");
 
+/* log2 latency histograms, keyed by (tgid, kind) */
+#define HIST_RUNQ  1   /* wake -> on-CPU delay (usec) */
+#define HIST_FUTEX 2   /* futex wait (usec) */
+#define HIST_FAULT 3   /* page fault -> resolved (usec) */
+#define HIST_IO    4   /* block request issue -> complete (usec) */
+#define HIST_KINDS 4
+
+struct hist_key {
+    __u32 tgid;
+    __u32 kind;
+};
+
+/* LRU so short-lived children under follow_new can't starve new tgids; entries are
+ * also dropped when the tgid exits */
+struct {
+    __uint(type, BPF_MAP_TYPE_LRU_HASH);
+    __uint(max_entries, 8192);
+    __type(key, struct hist_key);
+    __type(value, struct lat_hist);
+} LAT_HIST SEC(".maps");
+
+static __always_inline void hist_add(__u32 tgid, __u32 kind, __u64 val_us)
+{
+    struct hist_key k = { .tgid = tgid, .kind = kind };
+    struct lat_hist *h = bpf_map_lookup_elem(&LAT_HIST, &k);
+    if (!h) {
+        struct lat_hist zero = {};
+        bpf_map_update_elem(&LAT_HIST, &k, &zero, BPF_NOEXIST);
+        h = bpf_map_lookup_elem(&LAT_HIST, &k);
+        if (!h) return;
+    }
+    __u32 slot = log2_u64(val_us);
+    if (slot >= HIST_SLOTS) slot = HIST_SLOTS - 1;
+    __sync_fetch_and_add(&h->slots[slot], 1);
+}
+
+static __always_inline void hist_drop(__u32 tgid)
+{
+    for (__u32 kind = 1; kind <= HIST_KINDS; kind++) {
+        struct hist_key k = { .tgid = tgid, .kind = kind };
+        bpf_map_delete_elem(&LAT_HIST, &k);
+    }
+}
+
 struct {
     __uint(type, BPF_MAP_TYPE_ARRAY);
     __uint(max_entries, 1);
@@ -118,6 +162,13 @@ int BPF_PROG(ev_sched_waking, struct task_struct *p)
     if (!p) return 0;
     bpf_core_read(&wakee, sizeof(wakee), &p->pid);
 
+    // Remember the wake time of target tasks so sched_switch can measure runq delay
+    __u32 wakee_tgid = BPF_CORE_READ(p, tgid);
+    if (is_target_tgid(wakee_tgid)) {
+        __u64 now = ktime_ns();
+        bpf_map_update_elem(&TID_WAKE_TS, &wakee, &now, BPF_ANY);
+    }
+
     struct comm_event *e = bpf_ringbuf_reserve(&COMM_EVENTS, sizeof(*e), 0);
     if (!e) return 0;
     e->type = 1; e->pad = 0;
@@ -130,17 +181,34 @@ int BPF_PROG(ev_sched_waking, struct task_struct *p)
 SEC("tp_btf/sched_switch")
 int BPF_PROG(tp_switch, bool preempt, struct task_struct *prev, struct task_struct *next)
 {
+    __u64 now = ktime_ns();
+
+    /* close prev's on-CPU stretch first, whoever runs next */
+    __u32 prev_tgid = BPF_CORE_READ(prev, tgid);
+    if (is_target_tgid(prev_tgid)) {
+        __u32 prev_tid = BPF_CORE_READ(prev, pid);
+        struct TaskStats *pst = bpf_map_lookup_elem(&TID_STATS, &prev_tid);
+        if (pst && pst->last_oncpu_ts_ns != 0) {
+            __u64 delta_us = ns_to_us(now - pst->last_oncpu_ts_ns);
+            pst->total_oncpu_us += delta_us;
+            pst->nr_bursts++;
+            if (delta_us > pst->max_burst_us)
+                pst->max_burst_us = delta_us;
+            pst->last_oncpu_ts_ns = 0;
+        }
+    }
+
     __u32 next_tgid = BPF_CORE_READ(next, tgid);
     if (!is_target_tgid(next_tgid))
         return 0;
 
     __u32 next_tid = BPF_CORE_READ(next, pid);
-    __u64 now = ktime_ns();
 
     __u64 *wts = bpf_map_lookup_elem(&TID_WAKE_TS, &next_tid);
     if (wts) {
         __u64 delay_ns = now - *wts;
         __u64 delay_us = ns_to_us(delay_ns);
+        hist_add(next_tgid, HIST_RUNQ, delay_us);
         struct TaskStats *st = get_or_init_stats(next_tid);
         if (st) {
             ewma_update(&st->ewma_runq_us, delay_us);
@@ -149,17 +217,6 @@ int BPF_PROG(tp_switch, bool preempt, struct task_struct *prev, struct task_stru
         bpf_map_delete_elem(&TID_WAKE_TS, &next_tid);
     }
 
-    __u32 prev_tgid = BPF_CORE_READ(prev, tgid);
-    if (is_target_tgid(prev_tgid)) {
-        __u32 prev_tid = BPF_CORE_READ(prev, pid);
-        struct TaskStats *pst = bpf_map_lookup_elem(&TID_STATS, &prev_tid);
-        if (pst && pst->last_oncpu_ts_ns != 0) {
-            __u64 delta_us = ns_to_us(now - pst->last_oncpu_ts_ns);
-            pst->total_oncpu_us += delta_us;
-            pst->last_oncpu_ts_ns = 0;
-        }
-    }
-
     // Mark the start of on-CPU for next
     {
         struct TaskStats *nst = get_or_init_stats(next_tid);
@@ -215,6 +272,7 @@ int tp_exit_futex(void *ctx) {
   __u64 delta_us = ns_to_us_round_up(ktime_ns() - *ts);
   bpf_map_delete_elem(&FUTEX_TS, &tid);
   agg_add(delta_us, 0);
+  hist_add(tgid, HIST_FUTEX, delta_us);
   return 0;
 }
 
@@ -244,6 +302,7 @@ int tp_exit_futex_waitv(void *ctx)
     __u64 delta_us = ns_to_us_round_up(ktime_ns() - *ts);
     struct TaskStats *st = get_or_init_stats(tid);
     if (st) ewma_update(&st->ewma_futex_us, delta_us);
+    hist_add(tgid, HIST_FUTEX, delta_us);
     bpf_map_delete_elem(&FUTEX_TS, &tid);
     return 0;
 }
@@ -317,6 +376,45 @@ int raw_pf_user(void *ctx) {
     return tp_pf_user(ctx);
 }
 
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __uint(max_entries, 65536);
+    __type(key, __u32); // tid
+    __type(value, __u64); // ts_ns
+} FAULT_TS SEC(".maps");
+
+// page fault -> resolved latency, measured around handle_mm_fault
+SEC("kprobe/handle_mm_fault")
+int BPF_KPROBE(kp_handle_mm_fault)
+{
+    __u64 pt = bpf_get_current_pid_tgid();
+    __u32 tgid = pt >> 32;
+    if (!is_target_tgid(tgid))
+        return 0;
+
+    __u32 tid = (__u32)pt;
+    __u64 now = ktime_ns();
+    bpf_map_update_elem(&FAULT_TS, &tid, &now, BPF_ANY);
+    return 0;
+}
+
+SEC("kretprobe/handle_mm_fault")
+int BPF_KRETPROBE(krp_handle_mm_fault)
+{
+    __u64 pt = bpf_get_current_pid_tgid();
+    __u32 tgid = pt >> 32;
+    if (!is_target_tgid(tgid))
+        return 0;
+
+    __u32 tid = (__u32)pt;
+    __u64 *ts = bpf_map_lookup_elem(&FAULT_TS, &tid);
+    if (!ts) return 0;
+    __u64 delta_us = ns_to_us(ktime_ns() - *ts);
+    bpf_map_delete_elem(&FAULT_TS, &tid);
+    hist_add(tgid, HIST_FAULT, delta_us);
+    return 0;
+}
+
 
 SEC("tp_btf/sched_process_fork")
 int BPF_PROG(tp_proc_fork, struct task_struct *parent, struct task_struct *child)
@@ -336,7 +434,14 @@ SEC("tp_btf/sched_process_exit")
 int BPF_PROG(tp_proc_exit, struct task_struct *p)
 {
     __u32 tgid = BPF_CORE_READ(p, tgid);
-    bpf_map_delete_elem(&TARGET_TGIDS, &tgid);
+    __u32 pid = BPF_CORE_READ(p, pid);
+    /* fires per thread; only the group leader's exit ends the process */
+    if (pid != tgid)
+        return 0;
+    if (bpf_map_delete_elem(&TARGET_TGIDS, &tgid) == 0) {
+        hist_drop(tgid);
+        emit_evt(tgid, EVT_PROC_EXIT, 0);
+    }
     return 0;
 }
 
@@ -353,6 +458,8 @@ SEC("perf_event")
 int on_llc_miss(struct bpf_perf_event_data *ctx)
 {
     __u32 tgid = bpf_get_current_pid_tgid() >> 32;
+    if (!is_target_tgid(tgid))
+        return 0;
     __u64 *v = bpf_map_lookup_elem(&LLC_MISS, &tgid);
     if (!v) { __u64 one = 1; bpf_map_update_elem(&LLC_MISS, &tgid, &one, BPF_ANY); }
     else    { (*v)++; }
@@ -360,7 +467,16 @@ int on_llc_miss(struct bpf_perf_event_data *ctx)
 }
 
 
-struct io_pattern { __u64 last_sector; __u64 seq; __u64 rnd; };
+struct io_pattern {
+    __u64 last_sector;  /* end sector of the previous completion */
+    __u64 seq;
+    __u64 rnd;
+    __u64 bytes;
+    __u64 reqs;
+    __u64 lat_us_sum;
+    __u32 dev_major;    /* device of the last completion */
+    __u32 dev_minor;
+};
 struct {
     __uint(type, BPF_MAP_TYPE_LRU_HASH);
     __uint(max_entries, 4096);
@@ -368,21 +484,104 @@ struct {
     __type(value, struct io_pattern);
 } IO_PAT SEC(".maps");
 
-struct rq_complete_s { unsigned long long sector; };
-SEC("tracepoint/block/block_rq_complete")
-int on_rq_complete(struct rq_complete_s *ctx)
+/* submitter of an in-flight request, keyed by struct request * */
+struct rq_start { __u32 tgid; __u32 pad; __u64 ts_ns; };
+struct {
+    __uint(type, BPF_MAP_TYPE_LRU_HASH);
+    __uint(max_entries, 16384);
+    __type(key, __u64);
+    __type(value, struct rq_start);
+} RQ_START SEC(".maps");
+
+extern int LINUX_KERNEL_VERSION __kconfig;
+
+/* pre-5.16 kernels keep the gendisk in rq->rq_disk, newer ones only via rq->q->disk */
+struct request___x {
+    struct request_queue *q;
+    struct gendisk *rq_disk;
+} __attribute__((preserve_access_index));
+
+static __always_inline struct gendisk *rq_disk(struct request *rq)
+{
+    struct request___x *r = (void *)rq;
+    if (bpf_core_field_exists(r->rq_disk))
+        return BPF_CORE_READ(r, rq_disk);
+    return BPF_CORE_READ(r, q, disk);
+}
+
+static __always_inline int rq_track_start(struct request *rq, bool issue)
 {
+    __u64 key = (__u64)rq;
+    struct rq_start *st = bpf_map_lookup_elem(&RQ_START, &key);
+    if (st) {
+        /* inserted by the target earlier; issue may run in a kworker, keep the owner */
+        if (issue) st->ts_ns = ktime_ns();
+        return 0;
+    }
     __u32 tgid = bpf_get_current_pid_tgid() >> 32;
+    if (!is_target_tgid(tgid))
+        return 0;
+    struct rq_start v = { .tgid = tgid, .ts_ns = ktime_ns() };
+    bpf_map_update_elem(&RQ_START, &key, &v, BPF_ANY);
+    return 0;
+}
+
+/* v5.11 (a54895fa) dropped the request_queue argument from both tracepoints */
+SEC("tp_btf/block_rq_insert")
+int on_rq_insert(u64 *ctx)
+{
+    if (LINUX_KERNEL_VERSION >= KERNEL_VERSION(5, 11, 0))
+        return rq_track_start((void *)ctx[0], false);
+    return rq_track_start((void *)ctx[1], false);
+}
+
+SEC("tp_btf/block_rq_issue")
+int on_rq_issue(u64 *ctx)
+{
+    if (LINUX_KERNEL_VERSION >= KERNEL_VERSION(5, 11, 0))
+        return rq_track_start((void *)ctx[0], true);
+    return rq_track_start((void *)ctx[1], true);
+}
+
+SEC("tp_btf/block_rq_complete")
+int BPF_PROG(on_rq_complete, struct request *rq, int error, unsigned int nr_bytes)
+{
+    __u64 key = (__u64)rq;
+    struct rq_start *st = bpf_map_lookup_elem(&RQ_START, &key);
+    if (!st)
+        return 0;
+    __u32 tgid = st->tgid;
+    __u64 lat_us = ns_to_us(ktime_ns() - st->ts_ns);
+    bpf_map_delete_elem(&RQ_START, &key);
+
+    __u64 sector = BPF_CORE_READ(rq, __sector);
+    __u64 nr_sectors = nr_bytes >> 9;
+    struct gendisk *disk = rq_disk(rq);
+    __u32 major = BPF_CORE_READ(disk, major);
+    __u32 minor = BPF_CORE_READ(disk, first_minor);
+
+    hist_add(tgid, HIST_IO, lat_us);
+
     struct io_pattern *p = bpf_map_lookup_elem(&IO_PAT, &tgid);
     if (!p) {
-        struct io_pattern z = {}; z.last_sector = ctx->sector;
+        struct io_pattern z = {};
+        z.last_sector = sector + nr_sectors;
+        z.bytes = nr_bytes;
+        z.reqs = 1;
+        z.lat_us_sum = lat_us;
+        z.dev_major = major;
+        z.dev_minor = minor;
         bpf_map_update_elem(&IO_PAT, &tgid, &z, BPF_ANY);
         return 0;
     }
-    __u64 sector = ctx->sector;
     __u64 delta = (sector > p->last_sector) ? (sector - p->last_sector) : (p->last_sector - sector);
-    if (delta == 0 || delta < 64) p->seq++; else p->rnd++;
-    p->last_sector = sector;
+    if (delta < 64) p->seq++; else p->rnd++;
+    p->last_sector = sector + nr_sectors;
+    p->bytes += nr_bytes;
+    p->reqs++;
+    p->lat_us_sum += lat_us;
+    p->dev_major = major;
+    p->dev_minor = minor;
     return 0;
 }
 
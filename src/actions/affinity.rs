@@ -49,8 +49,35 @@ pub fn set_affinity(pid: i32, cpus: &Vec<usize>) -> Result<()> {
     Ok(())
 }
 
-pub fn spread_across_llc(_pid: i32) -> Result<()> { Ok(()) }
-pub fn compact_within_numa(_pid: i32) -> Result<()> { Ok(()) }
+fn set_affinity_all_threads(pid: i32, cpus: &Vec<usize>) -> Result<()> {
+    if cpus.is_empty() { return Ok(()); }
+    let tasks = fs::read_dir(format!("/proc/{}/task", pid)).with_context(|| format!("read /proc/{}/task", pid))?;
+    for e in tasks.flatten() {
+        if let Some(tid) = e.file_name().to_str().and_then(|s| s.parse::<i32>().ok()) {
+            let _ = set_affinity(tid, cpus);
+        }
+    }
+    Ok(())
+}
+
+fn thread_count(pid: i32) -> usize {
+    fs::read_dir(format!("/proc/{}/task", pid)).map(|rd| rd.count()).unwrap_or(1).max(1)
+}
+
+/// Pin the process's threads to one CPU per thread, spread over LLC domains.
+pub fn spread_across_llc(pid: i32) -> Result<()> {
+    let topo = crate::topology::Topology::load()?;
+    let cpus = crate::numa::pick_spread(thread_count(pid), &topo, crate::topology::Placement::Llc);
+    set_affinity_all_threads(pid, &cpus)
+}
+
+/// Pin the process's threads inside its dominant NUMA node, filling one LLC first.
+pub fn compact_within_numa(pid: i32) -> Result<()> {
+    let topo = crate::topology::Topology::load()?;
+    let node = crate::numa::dominant_node_for_pid(pid).unwrap_or(0);
+    let cpus = crate::numa::pick_compact(node, thread_count(pid), &topo, crate::topology::Placement::Llc);
+    set_affinity_all_threads(pid, &cpus)
+}
 
 pub fn write_cpuset_paths(cg: &str, cpus: &str, mems: Option<&str>) -> Result<()> {
     let cpu_path = format!("{}/cpuset.cpus", cg);
@@ -92,6 +119,13 @@ pub fn apply_cpus_with_mems(cg: &str, cpus: &[usize]) -> Result<()> {
 }
 
 
+/// Whether the agent can write `cg`'s cpuset (controller delegated and file writable).
+pub fn cpuset_writable(cg: &str) -> bool {
+    let path = format!("{}/cpuset.cpus", cg);
+    let Ok(c) = std::ffi::CString::new(path) else { return false };
+    unsafe { libc::access(c.as_ptr(), libc::W_OK) == 0 }
+}
+
 pub fn apply_cpus_per_task(cg: &str, cpus: &[usize]) -> Result<()> {
     let procs_path = format!("{}/cgroup.procs", cg);
     let list = std::fs::read_to_string(&procs_path)?;
@@ -0,0 +1,110 @@
+// src/actions/cache.rs
+use anyhow::Result;
+use lazy_static::lazy_static;
+use nix::fcntl::{posix_fadvise, PosixFadviseAdvice};
+use std::{collections::{HashMap, HashSet}, fs, fs::File, hash::Hash, os::fd::AsRawFd, sync::Mutex, time::{Duration, Instant}};
+use crate::pagecache;
+
+/// A counter as of the last eviction pass: its value, since when it has had that value,
+/// and when it was last looked at.
+struct Seen<T> { value: T, since: Instant, at: Instant }
+
+type SeenMap<K, T> = HashMap<K, Seen<T>>;
+
+/// Record `value` for `key` and return how long it has been unchanged; zero on first sight.
+fn unchanged_for<K: Hash + Eq, T: PartialEq>(map: &mut SeenMap<K, T>, key: K, value: T, now: Instant) -> Duration {
+    match map.get_mut(&key) {
+        Some(e) => {
+            e.at = now;
+            if e.value != value { (e.value, e.since) = (value, now); }
+            now.duration_since(e.since)
+        }
+        None => {
+            map.insert(key, Seen { value, since: now, at: now });
+            Duration::ZERO
+        }
+    }
+}
+
+#[derive(Default)]
+struct History {
+    procs: SeenMap<i32, Option<(u64, u64)>>,       // pid -> (rchar, read_bytes)
+    files: SeenMap<(u64, u64), (u64, Option<u64>)>, // (dev, ino) -> (cached, recently evicted)
+}
+
+lazy_static! {
+    static ref HISTORY: Mutex<History> = Mutex::new(History::default());
+}
+
+/// Files whose owners have been idle this long count as cold (AGENT_EVICT_COLD_SECS, default 60).
+/// atime can't be used: relatime/noatime mounts barely update it.
+pub fn cold_secs() -> u64 {
+    std::env::var("AGENT_EVICT_COLD_SECS").ok()
+        .and_then(|v| v.parse::<u64>().ok()).unwrap_or(60)
+}
+
+fn procs_of(cg: &str) -> Vec<i32> {
+    fs::read_to_string(format!("{}/cgroup.procs", cg)).unwrap_or_default()
+        .lines().filter_map(|l| l.trim().parse::<i32>().ok()).collect()
+}
+
+/// (rchar, read_bytes) of /proc/<pid>/io; None when unreadable.
+fn read_counters(pid: i32) -> Option<(u64, u64)> {
+    let s = fs::read_to_string(format!("/proc/{}/io", pid)).ok()?;
+    let field = |k: &str| s.lines().find_map(|l| l.strip_prefix(k)?.trim().parse::<u64>().ok());
+    Some((field("rchar:")?, field("read_bytes:")?))
+}
+
+/// Drop the clean page cache of cold files opened by processes of `cg`, skipping
+/// files the target uses, until `max_bytes` cached bytes were dropped. Steady residency
+/// alone would also describe a hot, fully cached file, so a file is cold only when every
+/// process of `cg` holding it has read nothing (rchar, read_bytes) for cold_secs and its
+/// residency and refaults (cachestat) have not moved for as long. First sightings only
+/// record. At most the MAX_FILES largest idle files are measured per call.
+/// Returns the bytes that were cached in the advised files. Mapped pages are not dropped
+/// by the kernel, so a file read through mmap keeps its hot pages.
+pub fn evict_cold(cg: &str, target_pid: i32, max_bytes: u64, dry: bool) -> Result<u64> {
+    let keep: HashSet<String> = pagecache::files_of_pid(target_pid).into_iter().collect();
+    let cold = Duration::from_secs(cold_secs());
+    let now = Instant::now();
+
+    let mut hist = HISTORY.lock().unwrap();
+    let mut idle_files = HashSet::new();
+    let mut busy_files = HashSet::new();
+    for pid in procs_of(cg) {
+        // unreadable counters never look idle
+        let counters = read_counters(pid);
+        let idle = counters.is_some() && unchanged_for(&mut hist.procs, pid, counters, now) >= cold;
+        let files = pagecache::files_of_pid(pid);
+        if idle { idle_files.extend(files); } else { busy_files.extend(files); }
+    }
+    let idle_files = idle_files.into_iter().filter(|p| !keep.contains(p) && !busy_files.contains(p));
+
+    let mut cands: Vec<(String, u64)> = Vec::new();
+    for path in pagecache::largest(idle_files) {
+        let Some(r) = pagecache::residency_of(&path) else { continue };
+        let quiet = unchanged_for(&mut hist.files, (r.dev, r.ino), (r.cached_bytes, r.recently_evicted_bytes), now);
+        if quiet >= cold && r.cached_bytes > 0 { cands.push((path, r.cached_bytes)); }
+    }
+    // forget processes and files not seen for a while (exited, closed, or busy)
+    hist.procs.retain(|_, e| now.duration_since(e.at) < cold * 4);
+    hist.files.retain(|_, e| now.duration_since(e.at) < cold * 4);
+    drop(hist);
+    cands.sort_by_key(|c| std::cmp::Reverse(c.1));
+
+    let mut dropped = 0u64;
+    for (path, cached) in cands {
+        if dropped >= max_bytes { break; }
+        if dry {
+            eprintln!("[dry-run] would evict {} ({} KiB cached) of {}", path, cached >> 10, cg);
+        } else {
+            let Ok(f) = File::open(&path) else { continue };
+            if let Err(e) = posix_fadvise(f.as_raw_fd(), 0, 0, PosixFadviseAdvice::POSIX_FADV_DONTNEED) {
+                eprintln!("[agent] fadvise(DONTNEED) {} failed: {e}", path);
+                continue;
+            }
+        }
+        dropped += cached;
+    }
+    Ok(dropped)
+}
@@ -1,18 +1,40 @@
+// src/actions/cpu.rs
 use anyhow::Result;
-use std::{fs, path::Path};
+use std::path::Path;
+use super::journal;
 
-pub fn set_weight(cg: &str, weight: u64, dry: bool) -> Result<()> {
-    let path = format!("{}/cpu.weight", cg);
+const MIN_QUOTA_US: u64 = 1000;
+
+fn write_cpu(cg: &str, file: &str, val: &str, dry: bool) -> Result<()> {
+    let path = format!("{}/{}", cg, file);
     if dry {
-        eprintln!("[dry] would set {} to {}", path, weight);
+        eprintln!("[dry-run] would write {} -> {}", path, val);
+        return Ok(());
+    }
+    if !crate::cgroups::ensure_controller(Path::new(cg), "cpu")? || !Path::new(&path).exists() {
+        eprintln!("[agent] {} not available; skipping", path);
         return Ok(());
     }
-    if Path::new(&path).exists() {
-        if let Err(e) = fs::write(&path, weight.to_string()) {
-            eprintln!("[warn] set cpu.weight={} failed on {}: {e}", weight, path);
-        }
-    } else {
-        eprintln!("[warn] {} not present; skipping cpu.weight", path);
+    journal::write(&path, val)
+}
+
+/// cpu.max bandwidth limit; `quota_us: None` removes the limit.
+pub fn set_max(cg: &str, quota_us: Option<u64>, period_us: u64, dry: bool) -> Result<()> {
+    let period = period_us.clamp(1000, 1_000_000);
+    let quota = quota_us
+        .map(|q| q.max(MIN_QUOTA_US).to_string())
+        .unwrap_or_else(|| "max".to_string());
+    write_cpu(cg, "cpu.max", &format!("{} {}", quota, period), dry)
+}
+
+/// cpu.uclamp.min / cpu.uclamp.max in percent; `None` leaves a bound untouched.
+pub fn set_uclamp(cg: &str, min_pct: Option<f64>, max_pct: Option<f64>, dry: bool) -> Result<()> {
+    if let Some(m) = min_pct {
+        write_cpu(cg, "cpu.uclamp.min", &format!("{:.2}", m.clamp(0.0, 100.0)), dry)?;
+    }
+    if let Some(m) = max_pct {
+        let v = if m >= 100.0 { "max".to_string() } else { format!("{:.2}", m.max(0.0)) };
+        write_cpu(cg, "cpu.uclamp.max", &v, dry)?;
     }
     Ok(())
-}
\ No newline at end of file
+}
@@ -1,12 +1,67 @@
 // src/actions/io.rs
 use anyhow::{Result, Context};
-use std::fs;
-pub fn tune(dev: &str, readahead_kb: u32, scheduler: Option<&str>) -> Result<()> {
-    let ra = format!("/sys/block/{}/queue/read_ahead_kb", dev);
-    fs::write(&ra, format!("{}", readahead_kb)).with_context(|| format!("write {}", ra))?;
-    if let Some(s) = scheduler {
-        let sch = format!("/sys/block/{}/queue/scheduler", dev);
-        fs::write(&sch, s).with_context(|| format!("write {}", sch))?;
+use std::{fs, path::Path};
+use super::journal;
+
+/// Queue knobs for one block device; `None` leaves a knob untouched.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlockTune {
+    pub readahead_kb: Option<u32>,
+    pub scheduler: Option<String>,
+    pub nr_requests: Option<u32>,
+    pub rq_affinity: Option<u8>,
+    pub nomerges: Option<u8>,
+}
+
+/// Map a partition (sda1, nvme0n1p2) to its parent disk; whole disks map to themselves.
+pub fn parent_disk(dev: &str) -> String {
+    let sys = format!("/sys/class/block/{}", dev);
+    if Path::new(&format!("{}/partition", sys)).exists() {
+        if let Ok(real) = fs::canonicalize(&sys) {
+            if let Some(parent) = real.parent().and_then(|p| p.file_name()).and_then(|s| s.to_str()) {
+                return parent.to_string();
+            }
+        }
+    }
+    dev.to_string()
+}
+
+fn write_knob(dev: &str, knob: &str, val: &str, dry: bool) -> Result<()> {
+    let path = format!("/sys/block/{}/queue/{}", dev, knob);
+    if dry {
+        eprintln!("[dry-run] would write {} -> {}", path, val);
+        return Ok(());
+    }
+    if !Path::new(&path).exists() {
+        eprintln!("[warn] {} not present; skipping", path);
+        return Ok(());
+    }
+    journal::write(&path, val)
+}
+
+pub fn tune(dev: &str, t: &BlockTune, dry: bool) -> Result<()> {
+    let disk = parent_disk(dev);
+    if let Some(s) = t.scheduler.as_deref() {
+        // nr_requests is bounded by the scheduler's depth, so switch first
+        let avail = fs::read_to_string(format!("/sys/block/{}/queue/scheduler", disk)).unwrap_or_default();
+        if avail.split_whitespace().any(|x| x.trim_matches(|c| c == '[' || c == ']') == s) {
+            write_knob(&disk, "scheduler", s, dry)?;
+        } else {
+            eprintln!("[warn] scheduler '{}' not available on {}: {}", s, disk, avail.trim());
+        }
+    }
+    if let Some(ra) = t.readahead_kb {
+        write_knob(&disk, "read_ahead_kb", &ra.to_string(), dry)?;
+    }
+    if let Some(n) = t.nr_requests {
+        write_knob(&disk, "nr_requests", &n.max(4).to_string(), dry)
+            .with_context(|| format!("nr_requests on {}", disk))?;
+    }
+    if let Some(a) = t.rq_affinity {
+        write_knob(&disk, "rq_affinity", &a.min(2).to_string(), dry)?;
+    }
+    if let Some(m) = t.nomerges {
+        write_knob(&disk, "nomerges", &m.min(2).to_string(), dry)?;
     }
     Ok(())
-}
\ No newline at end of file
+}
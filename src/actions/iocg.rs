@@ -0,0 +1,89 @@
+// src/actions/iocg.rs
+use anyhow::{bail, Result};
+use std::{fs, path::Path};
+use super::{io::parent_disk, journal};
+
+/// "MAJ:MIN" of the whole disk behind `dev` (io.* files reject partitions).
+pub fn dev_majmin(dev: &str) -> Option<String> {
+    let disk = parent_disk(dev);
+    fs::read_to_string(format!("/sys/class/block/{}/dev", disk)).ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| s.contains(':'))
+}
+
+fn prepare(cg: &str, file: &str, dry: bool) -> Result<Option<String>> {
+    let path = format!("{}/{}", cg, file);
+    if dry {
+        return Ok(Some(path));
+    }
+    if !crate::cgroups::ensure_controller(Path::new(cg), "io")? {
+        eprintln!("[agent] io controller not available in {}; skipping {}", cg, file);
+        return Ok(None);
+    }
+    if !Path::new(&path).exists() {
+        eprintln!("[warn] {} not present; skipping", path);
+        return Ok(None);
+    }
+    Ok(Some(path))
+}
+
+/// Current entry for `majmin` in a keyed io.* file, if any.
+fn current_line(path: &str, majmin: &str) -> Option<String> {
+    fs::read_to_string(path).ok()?
+        .lines()
+        .find(|l| l.split_whitespace().next() == Some(majmin))
+        .map(|l| l.trim().to_string())
+}
+
+/// io.weight: `dev == None` sets the cgroup default, otherwise a per-device override.
+pub fn set_weight(cg: &str, weight: u32, dev: Option<&str>, dry: bool) -> Result<()> {
+    let w = weight.clamp(1, 10000);
+    let Some(path) = prepare(cg, "io.weight", dry)? else { return Ok(()) };
+    let (key, line, undo) = match dev {
+        None => {
+            let cur = fs::read_to_string(&path).unwrap_or_default();
+            let def = cur.lines()
+                .find_map(|l| l.strip_prefix("default ").map(|v| v.trim().to_string()))
+                .unwrap_or_else(|| "100".to_string());
+            ("default".to_string(), format!("default {}", w), format!("default {}", def))
+        }
+        Some(d) => {
+            let Some(mm) = dev_majmin(d) else { bail!("cannot resolve MAJ:MIN for {}", d) };
+            let undo = current_line(&path, &mm).unwrap_or_else(|| format!("{} default", mm));
+            (mm.clone(), format!("{} {}", mm, w), undo)
+        }
+    };
+    if dry {
+        eprintln!("[dry-run] would write {} -> {}", path, line);
+        return Ok(());
+    }
+    journal::write_with_undo(&path, &key, &line, &undo)
+}
+
+/// io.max limits; `None` means "max" (unlimited).
+pub fn set_max(cg: &str, dev: &str, rbps: Option<u64>, wbps: Option<u64>, riops: Option<u64>, wiops: Option<u64>, dry: bool) -> Result<()> {
+    let Some(mm) = dev_majmin(dev) else { bail!("cannot resolve MAJ:MIN for {}", dev) };
+    let Some(path) = prepare(cg, "io.max", dry)? else { return Ok(()) };
+    let lim = |v: Option<u64>| v.map(|x| x.to_string()).unwrap_or_else(|| "max".to_string());
+    let line = format!("{} rbps={} wbps={} riops={} wiops={}", mm, lim(rbps), lim(wbps), lim(riops), lim(wiops));
+    if dry {
+        eprintln!("[dry-run] would write {} -> {}", path, line);
+        return Ok(());
+    }
+    let undo = current_line(&path, &mm)
+        .unwrap_or_else(|| format!("{} rbps=max wbps=max riops=max wiops=max", mm));
+    journal::write_with_undo(&path, &mm, &line, &undo)
+}
+
+/// io.latency target in usec; 0 removes the target.
+pub fn set_latency(cg: &str, dev: &str, target_us: u64, dry: bool) -> Result<()> {
+    let Some(mm) = dev_majmin(dev) else { bail!("cannot resolve MAJ:MIN for {}", dev) };
+    let Some(path) = prepare(cg, "io.latency", dry)? else { return Ok(()) };
+    let line = format!("{} target={}", mm, target_us);
+    if dry {
+        eprintln!("[dry-run] would write {} -> {}", path, line);
+        return Ok(());
+    }
+    let undo = current_line(&path, &mm).unwrap_or_else(|| format!("{} target=0", mm));
+    journal::write_with_undo(&path, &mm, &line, &undo)
+}
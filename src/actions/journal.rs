@@ -0,0 +1,75 @@
+// src/actions/journal.rs
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use std::{collections::HashSet, fs, sync::Mutex};
+
+lazy_static! {
+    // (path, value) found before the agent first wrote it, in write order
+    static ref ORIGINAL: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+    static ref SEEN: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+/// sysfs selector files ("mq-deadline [none] kyber") are restored by their active entry.
+fn normalize(raw: &str) -> String {
+    let t = raw.trim();
+    if let (Some(a), Some(b)) = (t.find('['), t.find(']')) {
+        if a < b { return t[a + 1..b].to_string(); }
+    }
+    t.to_string()
+}
+
+/// Remember the current value of `path` once, so `rollback` can restore it.
+pub fn record(path: &str) {
+    let mut seen = SEEN.lock().unwrap();
+    if !seen.insert(path.to_string()) { return; }
+    if let Ok(cur) = fs::read_to_string(path) {
+        ORIGINAL.lock().unwrap().push((path.to_string(), normalize(&cur)));
+    }
+}
+
+/// Journaled write for multi-entry files (io.max, io.weight) where the undo
+/// payload has to be built by the caller; `key` identifies the entry within `path`.
+pub fn write_with_undo(path: &str, key: &str, value: &str, undo: &str) -> Result<()> {
+    let id = format!("{}#{}", path, key);
+    if SEEN.lock().unwrap().insert(id) {
+        ORIGINAL.lock().unwrap().push((path.to_string(), undo.to_string()));
+    }
+    fs::write(path, value).with_context(|| format!("write {}='{}'", path, value.trim()))
+}
+
+/// Journaled write: record the original value, then write the new one.
+pub fn write(path: &str, value: &str) -> Result<()> {
+    record(path);
+    fs::write(path, value).with_context(|| format!("write {}='{}'", path, value.trim()))
+}
+
+/// Drop `path` from the journal, returning the value recorded for it.
+fn take(path: &str) -> Option<String> {
+    SEEN.lock().unwrap().remove(path);
+    let mut orig = ORIGINAL.lock().unwrap();
+    let i = orig.iter().position(|(p, _)| p == path)?;
+    Some(orig.remove(i).1)
+}
+
+/// Forget `path` without writing it, e.g. when the journaled write itself was refused.
+pub fn forget(path: &str) {
+    take(path);
+}
+
+/// Put `path` back to its recorded value before shutdown; Ok(false) if it was never written.
+pub fn restore(path: &str) -> Result<bool> {
+    let Some(val) = take(path) else { return Ok(false) };
+    fs::write(path, &val).with_context(|| format!("restore {}='{}'", path, val))?;
+    Ok(true)
+}
+
+/// Restore every journaled file, newest first. Best-effort.
+pub fn rollback() {
+    let entries: Vec<(String, String)> = ORIGINAL.lock().unwrap().drain(..).collect();
+    SEEN.lock().unwrap().clear();
+    for (path, val) in entries.into_iter().rev() {
+        if let Err(e) = fs::write(&path, &val) {
+            eprintln!("[agent] rollback {} -> '{}' failed: {e}", path, val);
+        }
+    }
+}
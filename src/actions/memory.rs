@@ -0,0 +1,120 @@
+// src/actions/memory.rs
+use anyhow::{Context, Result};
+use std::{collections::HashMap, fs, path::Path};
+use super::journal;
+
+const MIN_HIGH_BYTES: u64 = 64 << 20;
+
+/// memory.current plus the memory.stat fields the bounds are derived from.
+#[derive(Debug, Clone, Default)]
+pub struct MemState {
+    pub current: u64,
+    pub anon: u64,
+    pub file: u64,
+    pub active_file: u64,
+    pub inactive_file: u64,
+    pub inactive_anon: u64,
+}
+
+impl MemState {
+    pub fn read(cg: &str) -> Option<Self> {
+        let current = fs::read_to_string(format!("{}/memory.current", cg)).ok()?
+            .trim().parse::<u64>().ok()?;
+        let stat = read_stat(cg);
+        let g = |k: &str| stat.get(k).copied().unwrap_or(0);
+        Some(Self {
+            current,
+            anon: g("anon"),
+            file: g("file"),
+            active_file: g("active_file"),
+            inactive_file: g("inactive_file"),
+            inactive_anon: g("inactive_anon"),
+        })
+    }
+
+    /// Rough working set: everything except inactive page cache.
+    pub fn working_set(&self) -> u64 {
+        self.current.saturating_sub(self.inactive_file)
+    }
+}
+
+pub fn read_stat(cg: &str) -> HashMap<String, u64> {
+    let mut out = HashMap::new();
+    if let Ok(s) = fs::read_to_string(format!("{}/memory.stat", cg)) {
+        for line in s.lines() {
+            if let Some((k, v)) = line.split_once(' ') {
+                if let Ok(v) = v.trim().parse::<u64>() { out.insert(k.to_string(), v); }
+            }
+        }
+    }
+    out
+}
+
+fn fmt_limit(v: Option<u64>) -> String {
+    v.map(|b| b.to_string()).unwrap_or_else(|| "max".to_string())
+}
+
+fn write_mem(cg: &str, file: &str, val: &str, dry: bool) -> Result<()> {
+    let path = format!("{}/{}", cg, file);
+    if dry {
+        eprintln!("[dry-run] would write {} -> {}", path, val);
+        return Ok(());
+    }
+    if !crate::cgroups::ensure_controller(Path::new(cg), "memory")? || !Path::new(&path).exists() {
+        eprintln!("[agent] {} not available; skipping", path);
+        return Ok(());
+    }
+    journal::write(&path, val)
+}
+
+/// memory.high throttling; never set below the working set (+10%) or 64 MiB.
+pub fn set_high(cg: &str, bytes: Option<u64>, dry: bool) -> Result<()> {
+    let bytes = match (bytes, MemState::read(cg)) {
+        (Some(b), Some(st)) => Some(b.max(st.working_set() + st.working_set() / 10).max(MIN_HIGH_BYTES)),
+        (Some(b), None) => Some(b.max(MIN_HIGH_BYTES)),
+        (None, _) => None,
+    };
+    write_mem(cg, "memory.high", &fmt_limit(bytes), dry)
+}
+
+/// memory.low / memory.min protection, capped at what the cgroup currently uses.
+pub fn set_protection(cg: &str, low: Option<u64>, min: Option<u64>, dry: bool) -> Result<()> {
+    let cap = MemState::read(cg).map(|s| s.current).unwrap_or(u64::MAX);
+    if let Some(l) = low {
+        write_mem(cg, "memory.low", &l.min(cap).to_string(), dry)?;
+    }
+    if let Some(m) = min {
+        // memory.min is hard protection; keep it at or below memory.low
+        let m = m.min(cap).min(low.unwrap_or(u64::MAX));
+        write_mem(cg, "memory.min", &m.to_string(), dry)?;
+    }
+    Ok(())
+}
+
+pub fn set_swap_max(cg: &str, bytes: Option<u64>, dry: bool) -> Result<()> {
+    write_mem(cg, "memory.swap.max", &fmt_limit(bytes), dry)
+}
+
+/// Proactive reclaim, bounded by inactive memory and 10% of memory.current.
+/// memory.reclaim is write-only and not journaled.
+pub fn reclaim(cg: &str, bytes: u64, dry: bool) -> Result<()> {
+    let path = format!("{}/memory.reclaim", cg);
+    let Some(st) = MemState::read(cg) else { return Ok(()) };
+    let bound = (st.inactive_file + st.inactive_anon).min(st.current / 10);
+    let b = bytes.min(bound);
+    if b == 0 { return Ok(()); }
+    if dry {
+        eprintln!("[dry-run] would write {} -> {}", path, b);
+        return Ok(());
+    }
+    if !Path::new(&path).exists() {
+        eprintln!("[warn] {} not present (kernel < 5.19?); skipping", path);
+        return Ok(());
+    }
+    // EAGAIN just means the kernel reclaimed less than asked
+    match fs::write(&path, b.to_string()) {
+        Ok(_) => Ok(()),
+        Err(e) if e.raw_os_error() == Some(libc::EAGAIN) => Ok(()),
+        Err(e) => Err(e).with_context(|| format!("write {}", path)),
+    }
+}
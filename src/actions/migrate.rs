@@ -0,0 +1,143 @@
+// src/actions/migrate.rs
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::{fs, sync::Mutex};
+
+const MPOL_MF_MOVE: libc::c_int = 1 << 1;
+const CHUNK: usize = 512;
+
+/// Progress of the current memory migration, reported in the Snapshot.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct MigrationProgress {
+    pub to_node: u32,
+    pub moved_bytes_last: u64,
+    pub moved_bytes_total: u64,
+    pub pages_off_node: u64,
+    pub failed_pages: u64,
+}
+
+lazy_static! {
+    static ref PROGRESS: Mutex<Option<MigrationProgress>> = Mutex::new(None);
+}
+
+pub fn progress() -> Option<MigrationProgress> {
+    PROGRESS.lock().unwrap().clone()
+}
+
+/// Per-interval cap in bytes (AGENT_MIGRATE_MB_PER_TICK, default 64 MiB).
+pub fn budget_bytes() -> u64 {
+    std::env::var("AGENT_MIGRATE_MB_PER_TICK").ok()
+        .and_then(|v| v.parse::<u64>().ok()).unwrap_or(64) << 20
+}
+
+fn page_size() -> u64 {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as u64 }
+}
+
+/// VMA ranges from /proc/<pid>/maps keyed by start address.
+fn vma_ranges(pid: i32) -> Result<Vec<(u64, u64)>> {
+    let maps = fs::read_to_string(format!("/proc/{}/maps", pid))
+        .with_context(|| format!("read /proc/{}/maps", pid))?;
+    let mut out = Vec::new();
+    for line in maps.lines() {
+        let Some(range) = line.split_whitespace().next() else { continue };
+        let Some((a, b)) = range.split_once('-') else { continue };
+        if let (Ok(a), Ok(b)) = (u64::from_str_radix(a, 16), u64::from_str_radix(b, 16)) {
+            out.push((a, b));
+        }
+    }
+    Ok(out)
+}
+
+fn move_pages(pid: i32, pages: &[*mut libc::c_void], nodes: Option<&[libc::c_int]>, status: &mut [libc::c_int]) -> i64 {
+    unsafe {
+        libc::syscall(
+            libc::SYS_move_pages,
+            pid,
+            pages.len() as libc::c_ulong,
+            pages.as_ptr(),
+            nodes.map(|n| n.as_ptr()).unwrap_or(std::ptr::null()),
+            status.as_mut_ptr(),
+            MPOL_MF_MOVE,
+        ) as i64
+    }
+}
+
+/// EPERM right after a failed move_pages: a foreign-uid target without CAP_SYS_NICE.
+/// That is a normal deployment, so the migration is skipped rather than failed.
+fn denied(pid: i32) -> bool {
+    if std::io::Error::last_os_error().raw_os_error() != Some(libc::EPERM) { return false; }
+    eprintln!("[agent] move_pages on pid {} denied (CAP_SYS_NICE); skipping migration", pid);
+    true
+}
+
+/// Move up to `max_bytes` of `pid`'s resident pages onto `to_node` with move_pages(2),
+/// visiting only VMAs that numa_maps reports as having pages elsewhere.
+pub fn migrate_to_node(pid: i32, to_node: u32, max_bytes: u64, dry: bool) -> Result<()> {
+    let psz = page_size();
+    let budget_pages = (max_bytes.min(budget_bytes()) / psz) as usize;
+    let vmas = crate::numa::numa_maps_vmas(pid);
+    let off_node: u64 = vmas.iter()
+        .flat_map(|(_, c)| c.iter().filter(|(n, _)| **n != to_node).map(|(_, v)| *v))
+        .sum();
+
+    let mut prog = PROGRESS.lock().unwrap().clone()
+        .filter(|p| p.to_node == to_node)
+        .unwrap_or(MigrationProgress { to_node, ..Default::default() });
+    prog.pages_off_node = off_node;
+    prog.moved_bytes_last = 0;
+
+    if dry {
+        eprintln!("[dry-run] would migrate up to {} pages of pid {} to node {} ({} off-node)", budget_pages, pid, to_node, off_node);
+        *PROGRESS.lock().unwrap() = Some(prog);
+        return Ok(());
+    }
+
+    let mut ranges = vma_ranges(pid)?;
+    ranges.sort_unstable();
+    let mut moved = 0usize;
+    let mut failed = 0u64;
+    'vmas: for (start, counts) in &vmas {
+        if counts.keys().all(|n| *n == to_node) { continue; }
+        let Ok(i) = ranges.binary_search_by_key(start, |&(a, _)| a) else { continue };
+        let (a, b) = ranges[i];
+        let mut addr = a;
+        while addr < b {
+            if moved >= budget_pages { break 'vmas; }
+            let n = (((b - addr) / psz) as usize).min(CHUNK);
+            let pages: Vec<*mut libc::c_void> = (0..n).map(|i| (addr + i as u64 * psz) as *mut libc::c_void).collect();
+            addr += n as u64 * psz;
+
+            // query where each page currently lives, then move only the off-node resident ones
+            let mut status = vec![0 as libc::c_int; n];
+            if move_pages(pid, &pages, None, &mut status) < 0 {
+                if denied(pid) { break 'vmas; }
+                continue;
+            }
+            let want: Vec<*mut libc::c_void> = pages.iter().zip(&status)
+                .filter(|(_, s)| **s >= 0 && **s as u32 != to_node)
+                .map(|(p, _)| *p)
+                .take(budget_pages - moved)
+                .collect();
+            if want.is_empty() { continue; }
+            let nodes = vec![to_node as libc::c_int; want.len()];
+            let mut st = vec![0 as libc::c_int; want.len()];
+            if move_pages(pid, &want, Some(&nodes), &mut st) < 0 {
+                if denied(pid) { break 'vmas; }
+                failed += want.len() as u64;
+                continue;
+            }
+            let ok = st.iter().filter(|s| **s == to_node as libc::c_int).count();
+            moved += ok;
+            failed += (want.len() - ok) as u64;
+        }
+    }
+    let bytes = moved as u64 * psz;
+    prog.moved_bytes_last = bytes;
+    prog.moved_bytes_total += bytes;
+    prog.pages_off_node = off_node.saturating_sub(moved as u64);
+    prog.failed_pages += failed;
+    *PROGRESS.lock().unwrap() = Some(prog);
+    Ok(())
+}
@@ -5,6 +5,18 @@ pub mod affinity;
 pub mod weight;
 pub mod priority;
 pub mod prefetch;
+pub mod io;
+pub mod journal;
+pub mod iocg;
+pub mod memory;
+pub mod cpu;
+pub mod migrate;
+pub mod pmadv;
+pub mod thp;
+pub mod cache;
+pub mod net;
+pub mod sched;
+pub mod rt;
 
 #[derive(Debug, Clone)]
 pub enum Action {
@@ -14,42 +26,253 @@ pub enum Action {
 
     // New:
     SetCpuWeight { weight: u32 },
+    /// cpu.weight back to its value before the agent first set it (undoes a neighbour demotion).
+    RestoreCpuWeight,
     SetNice { prio: i32 },
     SetIoPriority { class: i32, prio: i32 },
     SetSchedBatch { enable: bool },
+    /// SCHED_IDLE for every thread of the target (SCHED_OTHER when disabled).
+    SetSchedIdle { enable: bool },
+    /// /proc/<tid>/timerslack_ns for every thread of the target; 0 = default slack.
+    SetTimerSlack { ns: u64 },
+    /// Per-thread sched_setattr utilization clamps, 0..=1024.
+    SetTaskUclamp { min: Option<u32>, max: Option<u32> },
+    /// Per-thread latency nice (-20..19), on kernels with the latency-nice extension.
+    SetLatencyNice { nice: i32 },
     CompactWithinNUMA { node: Option<u32> },
     SpreadAcrossNUMA { width: usize },
+    /// Block queue knobs; an empty `dev` means the target's backing device.
+    TuneBlockDev {
+        dev: String,
+        readahead_kb: Option<u32>,
+        scheduler: Option<String>,
+        nr_requests: Option<u32>,
+        rq_affinity: Option<u8>,
+        nomerges: Option<u8>,
+    },
+    /// cgroup v2 io.weight; `dev: None` sets the default weight.
+    SetIoWeight { weight: u32, dev: Option<String> },
+    /// cgroup v2 io.max on `cgroup` (empty = target); `None` limits mean unlimited.
+    SetIoMax { cgroup: String, dev: String, rbps: Option<u64>, wbps: Option<u64>, riops: Option<u64>, wiops: Option<u64> },
+    /// cgroup v2 io.latency target for the target cgroup.
+    SetIoLatency { dev: String, target_us: u64 },
+    /// memory.high on `cgroup` (empty = target); `None` = max.
+    SetMemoryHigh { cgroup: String, bytes: Option<u64> },
+    /// memory.low / memory.min protection of the target against neighbours.
+    ProtectMemory { low: Option<u64>, min: Option<u64> },
+    /// memory.swap.max on `cgroup` (empty = target); `None` = max.
+    SetSwapMax { cgroup: String, bytes: Option<u64> },
+    /// Proactive memory.reclaim on `cgroup` (empty = target).
+    ReclaimMemory { cgroup: String, bytes: u64 },
+    /// cpu.max on `cgroup` (empty = target); `quota_us: None` = unlimited.
+    SetCpuMax { cgroup: String, quota_us: Option<u64>, period_us: u64 },
+    /// cpu.uclamp.{min,max} in percent for the target cgroup.
+    SetCpuUclamp { min_pct: Option<f64>, max_pct: Option<f64> },
+    /// Apply `action` to another cgroup (e.g. a noisy neighbour) instead of the target.
+    Scoped { cgroup: String, action: Box<Action> },
+    /// Opt-in SCHED_FIFO/RR/DEADLINE for specific target threads, bounded by rt::RtLimits.
+    PromoteRt { threads: Vec<(i32, rt::RtPolicy)> },
+    /// Apply a per-thread `action` only to threads whose comm matches `comm` (e.g. "worker-*").
+    Threads { comm: String, action: Box<Action> },
+    /// Move up to `max_bytes` of the target's memory onto `to_node` (move_pages).
+    MigrateMemory { to_node: u32, max_bytes: u64 },
+    /// sched_setaffinity for every task of `cgroup` (empty = target), used when cpusets are off-limits.
+    SetAffinity { cgroup: String, cpus: Vec<usize> },
+    /// MADV_COLLAPSE the target's hottest anonymous VMAs into huge pages (process_madvise).
+    CollapseThp { max_vmas: usize },
+    /// POSIX_FADV_DONTNEED on cold files of a neighbour `cgroup`, up to `max_bytes` of cache.
+    EvictCache { cgroup: String, max_bytes: u64 },
+    /// IRQ affinity, RPS and XPS of `iface` (empty = default-route NIC) onto `cpus`.
+    SteerNet { iface: String, cpus: Vec<usize>, steer: net::Steer },
+}
+
+impl Action {
+    /// Whether `Scoped` can redirect this action to another cgroup: it must act through the
+    /// cgroup's files or threads only, not the target pid or the target's backing device.
+    pub fn scopable(&self) -> bool {
+        match self {
+            Action::SetCpuset { .. } | Action::SetCpuWeight { .. } | Action::RestoreCpuWeight | Action::SetNice { .. }
+            | Action::SetIoPriority { .. } | Action::SetSchedBatch { .. } | Action::SetSchedIdle { .. }
+            | Action::SetTimerSlack { .. } | Action::SetTaskUclamp { .. } | Action::SetLatencyNice { .. }
+            | Action::SetMemoryHigh { .. } | Action::ProtectMemory { .. } | Action::SetSwapMax { .. }
+            | Action::ReclaimMemory { .. } | Action::SetCpuMax { .. } | Action::SetCpuUclamp { .. }
+            | Action::SetAffinity { .. } => true,
+            Action::SetIoWeight { dev, .. } => dev.as_deref().map(|d| !d.is_empty()).unwrap_or(true),
+            Action::SetIoMax { dev, .. } | Action::SetIoLatency { dev, .. } => !dev.is_empty(),
+            Action::Threads { action, .. } | Action::Scoped { action, .. } => action.scopable(),
+            _ => false,
+        }
+    }
 }
 
 pub struct Applier {
     pub cg: String,
     pub dry: bool,
+    pub pid: i32,
+    /// comm pattern restricting per-thread actions; None = every thread
+    pub threads: Option<String>,
 }
 
 impl Applier {
-    pub fn apply_all(&self, acts: &[Action]) -> Result<()> {
+    /// Explicit device, or the target's backing device when empty.
+    fn resolve_dev(&self, dev: &str) -> Option<String> {
+        if !dev.is_empty() { return Some(dev.to_string()); }
+        let d = crate::metrics::detect_io_dev(self.pid);
+        if d.is_none() { eprintln!("[agent] no backing device for pid {}", self.pid); }
+        d
+    }
+
+    /// Explicit cgroup if the allow/deny policy permits it, or the target's cgroup when empty.
+    fn pick_cg<'a>(&'a self, cgroup: &'a str) -> Option<&'a str> {
+        if cgroup.is_empty() { return Some(&self.cg); }
+        if crate::cgroups::may_touch(cgroup) { return Some(cgroup); }
+        eprintln!("[agent] cgroup {} not allowed by policy; skipping", cgroup);
+        None
+    }
+
+    /// Apply every action; a failing knob is logged and skipped so one bad write
+    /// doesn't stop the rest. Returns the number of failed actions.
+    pub fn apply_all(&self, acts: &[Action]) -> usize {
+        let mut failed = 0;
         for a in acts {
-            match a {
-                Action::SetCpuset { cgroup, cpus } => {
-                    let cg = if cgroup.is_empty() { &self.cg } else { cgroup };
-                    affinity::apply_cpus_with_mems(cg, cpus)?;
-                }
-                Action::SetCpuWeight { weight } => {
-                    weight::set_weight(&self.cg, *weight, self.dry)?;
-                }
-                Action::SetNice { prio } => {
-                    priority::set_nice_for_cgroup(&self.cg, *prio)?;
-                }
-                Action::SetIoPriority { class, prio } => {
-                    priority::set_ioprio_for_cgroup(&self.cg, *class, *prio)?;
+            if let Err(e) = self.apply(a) {
+                eprintln!("[agent] {} failed: {e:#}", crate::rate_limit::stable_key(a));
+                failed += 1;
+            }
+        }
+        failed
+    }
+
+    fn apply(&self, a: &Action) -> Result<()> {
+        match a {
+            Action::SetCpuset { cgroup, cpus } => {
+                let Some(cg) = self.pick_cg(cgroup) else { return Ok(()) };
+                affinity::apply_cpus_with_mems(cg, cpus)?;
+            }
+            Action::SetCpuWeight { weight } => {
+                weight::set_weight(&self.cg, *weight, self.dry)?;
+            }
+            Action::RestoreCpuWeight => {
+                weight::restore_weight(&self.cg, self.dry)?;
+            }
+            Action::SetNice { prio } => {
+                priority::set_nice_for_cgroup(&self.cg, *prio, self.threads.as_deref())?;
+            }
+            Action::SetIoPriority { class, prio } => {
+                priority::set_ioprio_for_cgroup(&self.cg, *class, *prio, self.threads.as_deref())?;
+            }
+            Action::SetSchedBatch { enable } => {
+                priority::set_sched_batch_for_cgroup(&self.cg, *enable, self.threads.as_deref())?;
+            }
+            Action::SetSchedIdle { enable } => {
+                sched::set_sched_idle_for_cgroup(&self.cg, *enable, self.threads.as_deref(), self.dry)?;
+            }
+            Action::SetTimerSlack { ns } => {
+                sched::set_timerslack_for_cgroup(&self.cg, *ns, self.threads.as_deref(), self.dry)?;
+            }
+            Action::SetTaskUclamp { min, max } => {
+                sched::set_uclamp_for_cgroup(&self.cg, *min, *max, self.threads.as_deref(), self.dry)?;
+            }
+            Action::SetLatencyNice { nice } => {
+                sched::set_latency_nice_for_cgroup(&self.cg, *nice, self.threads.as_deref(), self.dry)?;
+            }
+            Action::CompactWithinNUMA { .. } | Action::SpreadAcrossNUMA { .. } => {}
+            Action::Prefetch(a) => { prefetch::exec(a)?; }
+            Action::TuneBlockDev { dev, readahead_kb, scheduler, nr_requests, rq_affinity, nomerges } => {
+                let Some(dev) = self.resolve_dev(dev) else { return Ok(()) };
+                let t = io::BlockTune {
+                    readahead_kb: *readahead_kb,
+                    scheduler: scheduler.clone(),
+                    nr_requests: *nr_requests,
+                    rq_affinity: *rq_affinity,
+                    nomerges: *nomerges,
+                };
+                io::tune(&dev, &t, self.dry)?;
+            }
+            Action::SetIoWeight { weight, dev } => {
+                let dev = match dev {
+                    Some(d) => match self.resolve_dev(d) { Some(d) => Some(d), None => return Ok(()) },
+                    None => None,
+                };
+                iocg::set_weight(&self.cg, *weight, dev.as_deref(), self.dry)?;
+            }
+            Action::SetIoMax { cgroup, dev, rbps, wbps, riops, wiops } => {
+                let Some(cg) = self.pick_cg(cgroup) else { return Ok(()) };
+                let Some(dev) = self.resolve_dev(dev) else { return Ok(()) };
+                iocg::set_max(cg, &dev, *rbps, *wbps, *riops, *wiops, self.dry)?;
+            }
+            Action::SetIoLatency { dev, target_us } => {
+                let Some(dev) = self.resolve_dev(dev) else { return Ok(()) };
+                iocg::set_latency(&self.cg, &dev, *target_us, self.dry)?;
+            }
+            Action::SetMemoryHigh { cgroup, bytes } => {
+                let Some(cg) = self.pick_cg(cgroup) else { return Ok(()) };
+                memory::set_high(cg, *bytes, self.dry)?;
+            }
+            Action::ProtectMemory { low, min } => {
+                memory::set_protection(&self.cg, *low, *min, self.dry)?;
+            }
+            Action::SetSwapMax { cgroup, bytes } => {
+                let Some(cg) = self.pick_cg(cgroup) else { return Ok(()) };
+                memory::set_swap_max(cg, *bytes, self.dry)?;
+            }
+            Action::ReclaimMemory { cgroup, bytes } => {
+                let Some(cg) = self.pick_cg(cgroup) else { return Ok(()) };
+                memory::reclaim(cg, *bytes, self.dry)?;
+            }
+            Action::SetCpuMax { cgroup, quota_us, period_us } => {
+                let Some(cg) = self.pick_cg(cgroup) else { return Ok(()) };
+                cpu::set_max(cg, *quota_us, *period_us, self.dry)?;
+            }
+            Action::SetCpuUclamp { min_pct, max_pct } => {
+                cpu::set_uclamp(&self.cg, *min_pct, *max_pct, self.dry)?;
+            }
+            Action::SetAffinity { cgroup, cpus } => {
+                let Some(cg) = self.pick_cg(cgroup) else { return Ok(()) };
+                if self.dry {
+                    eprintln!("[dry-run] would set affinity {:?} for tasks of {}", cpus, cg);
+                } else {
+                    affinity::apply_cpus_per_task(cg, cpus)?;
                 }
-                Action::SetSchedBatch { enable } => {
-                    priority::set_sched_batch_for_cgroup(&self.cg, *enable)?;
+            }
+            Action::CollapseThp { max_vmas } => {
+                thp::collapse(self.pid, *max_vmas, self.dry)?;
+            }
+            Action::MigrateMemory { to_node, max_bytes } => {
+                migrate::migrate_to_node(self.pid, *to_node, *max_bytes, self.dry)?;
+            }
+            Action::EvictCache { cgroup, max_bytes } => {
+                let Some(cg) = self.pick_cg(cgroup) else { return Ok(()) };
+                if cg == self.cg { return Ok(()); }
+                let n = cache::evict_cold(cg, self.pid, *max_bytes, self.dry)?;
+                if n > 0 { eprintln!("[agent] evicted {} KiB of cold page cache from {}", n >> 10, cg); }
+            }
+            Action::SteerNet { iface, cpus, steer } => {
+                let iface = if iface.is_empty() {
+                    match net::default_iface() {
+                        Some(i) => i,
+                        None => { eprintln!("[agent] no default-route interface; skipping net steering"); return Ok(()) }
+                    }
+                } else { iface.clone() };
+                net::steer(&iface, cpus, *steer, self.dry)?;
+            }
+            Action::Scoped { cgroup, action } => {
+                let Some(cg) = self.pick_cg(cgroup) else { return Ok(()) };
+                // the applier's pid is the target's; pid-based actions would hit the target, not `cg`
+                if !action.scopable() {
+                    anyhow::bail!("{} can't be scoped to {}", crate::rate_limit::stable_key(action), cg);
                 }
-                Action::CompactWithinNUMA { .. } | Action::SpreadAcrossNUMA { .. } => {}
-                Action::Prefetch(a) => { prefetch::exec(a)?; }
+                let inner = Applier { cg: cg.to_string(), dry: self.dry, pid: self.pid, threads: self.threads.clone() };
+                inner.apply(action)?;
+            }
+            Action::PromoteRt { threads } => {
+                rt::promote(&self.cg, threads, self.dry)?;
+            }
+            Action::Threads { comm, action } => {
+                let inner = Applier { cg: self.cg.clone(), dry: self.dry, pid: self.pid, threads: Some(comm.clone()) };
+                inner.apply(action)?;
             }
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
@@ -1 +1,152 @@
-// TODO: extra net controls beyond sockops
\ No newline at end of file
+// src/actions/net.rs
+use anyhow::Result;
+use lazy_static::lazy_static;
+use std::{collections::HashSet, fs, path::Path, sync::Mutex};
+use super::journal;
+use crate::topology::to_cpuset_list;
+
+/// A NIC's queues and interrupt lines as found in sysfs / procfs.
+#[derive(Debug, Clone, Default)]
+pub struct NicQueues {
+    pub iface: String,
+    pub rx: Vec<String>, // "rx-0", ...
+    pub tx: Vec<String>, // "tx-0", ...
+    pub irqs: Vec<u32>,
+}
+
+lazy_static! {
+    // interfaces and queue files already reported as unsteerable, so each tick doesn't repeat it
+    static ref WARNED: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+fn warn_once(key: &str, msg: impl FnOnce() -> String) {
+    if WARNED.lock().unwrap().insert(key.to_string()) {
+        eprintln!("[agent] {}", msg());
+    }
+}
+
+/// Interface of the default IPv4 route (AGENT_NET_IFACE overrides).
+pub fn default_iface() -> Option<String> {
+    if let Ok(i) = std::env::var("AGENT_NET_IFACE") {
+        if !i.is_empty() { return Some(i); }
+    }
+    let s = fs::read_to_string("/proc/net/route").ok()?;
+    s.lines().skip(1).find_map(|l| {
+        let mut f = l.split_whitespace();
+        let iface = f.next()?;
+        (f.next()? == "00000000").then(|| iface.to_string())
+    })
+}
+
+fn sorted_queues(dir: &str, prefix: &str) -> Vec<String> {
+    let mut q: Vec<(usize, String)> = fs::read_dir(dir).into_iter().flatten().flatten()
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            let n = name.strip_prefix(prefix)?.parse::<usize>().ok()?;
+            Some((n, name))
+        })
+        .collect();
+    q.sort();
+    q.into_iter().map(|(_, n)| n).collect()
+}
+
+/// IRQs of the device's MSI vectors, or /proc/interrupts lines naming the interface.
+fn irqs_of(iface: &str) -> Vec<u32> {
+    let mut irqs: Vec<u32> = fs::read_dir(format!("/sys/class/net/{}/device/msi_irqs", iface))
+        .into_iter().flatten().flatten()
+        .filter_map(|e| e.file_name().to_str()?.parse::<u32>().ok())
+        .collect();
+    if irqs.is_empty() {
+        let s = fs::read_to_string("/proc/interrupts").unwrap_or_default();
+        for l in s.lines() {
+            let Some((num, rest)) = l.trim_start().split_once(':') else { continue };
+            let Ok(n) = num.parse::<u32>() else { continue };
+            if rest.split_whitespace().last().map(|name| name.starts_with(iface)).unwrap_or(false) {
+                irqs.push(n);
+            }
+        }
+    }
+    // MSI lists include the admin/config vector; only those with an affinity file can be steered
+    irqs.retain(|i| Path::new(&format!("/proc/irq/{}/smp_affinity_list", i)).exists());
+    irqs.sort_unstable();
+    irqs
+}
+
+pub fn discover(iface: &str) -> Result<NicQueues> {
+    let qdir = format!("/sys/class/net/{}/queues", iface);
+    if !Path::new(&qdir).exists() {
+        anyhow::bail!("no such interface {}", iface);
+    }
+    Ok(NicQueues {
+        iface: iface.to_string(),
+        rx: sorted_queues(&qdir, "rx-"),
+        tx: sorted_queues(&qdir, "tx-"),
+        irqs: irqs_of(iface),
+    })
+}
+
+/// Kernel bitmap format: comma-separated 32-bit hex words, most significant first.
+pub fn cpu_mask_hex(cpus: &[usize]) -> String {
+    let max = cpus.iter().copied().max().unwrap_or(0);
+    let mut words = vec![0u32; max / 32 + 1];
+    for &c in cpus { words[c / 32] |= 1 << (c % 32); }
+    words.iter().rev().map(|w| format!("{:08x}", w)).collect::<Vec<_>>().join(",")
+}
+
+fn write(path: &str, val: &str, dry: bool) -> Result<()> {
+    if dry {
+        eprintln!("[dry-run] would write {} -> {}", path, val);
+        return Ok(());
+    }
+    if !Path::new(path).exists() {
+        eprintln!("[warn] {} not present; skipping", path);
+        return Ok(());
+    }
+    journal::write(path, val)
+}
+
+/// Which parts of the NIC to steer onto the target's CPUs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Steer { pub irq: bool, pub rps: bool, pub xps: bool }
+
+/// Point `iface`'s IRQs and XPS queues at `cpus` one CPU each (round robin),
+/// and let RPS spread received packets over all of `cpus`.
+pub fn steer(iface: &str, cpus: &[usize], what: Steer, dry: bool) -> Result<()> {
+    if cpus.is_empty() { return Ok(()); }
+    // a mistyped AGENT_NET_IFACE or a vanished veth is a config problem, not a failed tick
+    let nic = match discover(iface) {
+        Ok(n) => n,
+        Err(e) => { warn_once(iface, || format!("net steering skipped: {e:#}")); return Ok(()); }
+    };
+    if nic.irqs.is_empty() && nic.rx.is_empty() && nic.tx.is_empty() {
+        warn_once(iface, || format!("{} has no IRQs or queues to steer (virtual?); skipping", iface));
+        return Ok(());
+    }
+    if what.irq {
+        for (i, irq) in nic.irqs.iter().enumerate() {
+            let path = format!("/proc/irq/{}/smp_affinity_list", irq);
+            // managed IRQs reject affinity writes with EIO; leave those to the kernel
+            if let Err(e) = write(&path, &cpus[i % cpus.len()].to_string(), dry) {
+                warn_once(&path, || format!("irq {} of {}: {e:#}", irq, iface));
+            }
+        }
+    }
+    if what.rps {
+        let mask = cpu_mask_hex(cpus);
+        for q in &nic.rx {
+            let path = format!("/sys/class/net/{}/queues/{}/rps_cpus", iface, q);
+            // kernels without CONFIG_RPS and some virtual devices reject the write; skip that queue
+            if let Err(e) = write(&path, &mask, dry) { warn_once(&path, || format!("{}: {e:#}", path)); }
+        }
+    }
+    if what.xps {
+        for (i, q) in nic.tx.iter().enumerate() {
+            let mask = cpu_mask_hex(&[cpus[i % cpus.len()]]);
+            let path = format!("/sys/class/net/{}/queues/{}/xps_cpus", iface, q);
+            if let Err(e) = write(&path, &mask, dry) { warn_once(&path, || format!("{}: {e:#}", path)); }
+        }
+    }
+    eprintln!("[agent] steered {} ({} irqs, {} rx, {} tx) to cpus {}",
+        iface, nic.irqs.len(), nic.rx.len(), nic.tx.len(), to_cpuset_list(cpus));
+    Ok(())
+}
@@ -0,0 +1,30 @@
+// src/actions/pmadv.rs
+use std::io;
+use std::os::fd::{FromRawFd, OwnedFd, AsRawFd};
+
+pub const MADV_WILLNEED: libc::c_int = 3;
+pub const MADV_COLLAPSE: libc::c_int = 25;
+
+pub fn pidfd_open(pid: i32) -> io::Result<OwnedFd> {
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
+    if fd < 0 { return Err(io::Error::last_os_error()); }
+    // SAFETY: fresh fd returned by the kernel
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as i32) })
+}
+
+/// process_madvise(2) over `ranges` (addr, len) in another process; returns bytes advised.
+/// Ranges are submitted in batches of IOV_MAX.
+pub fn process_madvise(pidfd: &OwnedFd, ranges: &[(u64, u64)], advice: libc::c_int) -> io::Result<u64> {
+    let mut total = 0u64;
+    for chunk in ranges.chunks(libc::UIO_MAXIOV as usize) {
+        let iov: Vec<libc::iovec> = chunk.iter()
+            .map(|(a, l)| libc::iovec { iov_base: *a as *mut libc::c_void, iov_len: *l as usize })
+            .collect();
+        let r = unsafe {
+            libc::syscall(libc::SYS_process_madvise, pidfd.as_raw_fd(), iov.as_ptr(), iov.len(), advice, 0u32)
+        };
+        if r < 0 { return Err(io::Error::last_os_error()); }
+        total += r as u64;
+    }
+    Ok(total)
+}
@@ -1,76 +1,387 @@
-
 // src/actions/prefetch.rs
 use anyhow::{Result, Context};
-use std::{fs::File, os::fd::AsRawFd, os::unix::prelude::FromRawFd, collections::HashMap, time::Instant, io::Read};
+use std::{fs::File, os::fd::{AsRawFd, OwnedFd}, os::unix::fs::MetadataExt, collections::HashMap};
 use nix::fcntl::{posix_fadvise, PosixFadviseAdvice};
-use std::os::unix::io::RawFd;
 use std::sync::Mutex;
 use lazy_static::lazy_static;
+use super::pmadv;
+use crate::devt;
+use crate::hist::{HistSummary, Log2Hist};
+use serde::Serialize;
 
 #[derive(Debug, Clone)]
-pub enum PrefetchBackend { Fadvise, Readahead }
+pub enum PrefetchBackend { Fadvise, Readahead, ProcessMadvise }
 
 #[derive(Debug, Clone)]
 pub struct PrefetchAction {
     pub tgid: u32,
     pub dev: u64,
     pub ino: u64,
-    pub ranges: Vec<(u64,u64)>, // (offset, len)
+    pub ranges: Vec<(u64,u64)>, // (offset, len); virtual addresses when dev == ino == 0
     pub backend: PrefetchBackend,
 }
 
+/// Open files keyed by (tgid, dev, ino), dev in the kernel encoding BPF reports.
+/// Bounded (AGENT_PREFETCH_FD_CACHE, default 256) with least-recently-used eviction;
+/// entries of a tgid are dropped when it exits.
+struct FdCache {
+    cap: usize,
+    clock: u64,
+    map: HashMap<(u32,u64,u64), (OwnedFd, u64)>,
+}
+
+impl FdCache {
+    fn new() -> Self {
+        let cap = std::env::var("AGENT_PREFETCH_FD_CACHE").ok()
+            .and_then(|v| v.parse::<usize>().ok()).unwrap_or(256).max(1);
+        Self { cap, clock: 0, map: HashMap::new() }
+    }
+
+    fn get(&mut self, key: &(u32,u64,u64)) -> Option<&OwnedFd> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.map.get_mut(key).map(|(fd, used)| { *used = clock; &*fd })
+    }
+
+    fn insert(&mut self, key: (u32,u64,u64), fd: OwnedFd) {
+        if !self.map.contains_key(&key) && self.map.len() >= self.cap {
+            if let Some(old) = self.map.iter().min_by_key(|(_, (_, used))| *used).map(|(k, _)| *k) {
+                self.map.remove(&old);
+            }
+        }
+        self.clock += 1;
+        self.map.insert(key, (fd, self.clock));
+    }
+}
+
+const TOP_FILES: usize = 8;
+const PAGE: u64 = 4096;
+// issued ranges not yet collected by the model; bounds the backlog if nothing drains it
+const MAX_ISSUED: usize = 4096;
+
+/// Per-file prefetch accounting over the last interval.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PrefetchFileStat {
+    pub tgid: u32,
+    pub dev: u64,
+    pub ino: u64,
+    pub bytes: u64,
+    pub hits: u64,
+    pub waste: u64,
+}
+
+/// Prefetch effectiveness over the last interval, reported in the Snapshot.
+/// A hit is a predicted page that was later faulted; waste is one that never was.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PrefetchStats {
+    pub actions: u64,
+    pub bytes_requested: u64,
+    pub bytes_over_budget: u64,
+    pub budget_bytes: u64,
+    pub hits: u64,
+    pub waste: u64,
+    pub hit_rate: f64,
+    pub lead: HistSummary,
+    pub files: Vec<PrefetchFileStat>,
+    // target major faults this interval, and their EWMA over intervals with and without prefetch
+    pub majflt: u64,
+    pub majflt_ewma_active: f64,
+    pub majflt_ewma_idle: f64,
+}
+
+#[derive(Default)]
+struct Account {
+    actions: u64,
+    bytes: u64,
+    over_budget: u64,
+    lead: Log2Hist,
+    files: HashMap<(u32,u64,u64), PrefetchFileStat>,
+    majflt_active: f64,
+    majflt_idle: f64,
+}
+
+impl Account {
+    fn file(&mut self, tgid: u32, dev: u64, ino: u64) -> &mut PrefetchFileStat {
+        self.files.entry((tgid, dev, ino))
+            .or_insert_with(|| PrefetchFileStat { tgid, dev, ino, ..Default::default() })
+    }
+}
+
+/// Per-interval cap in bytes (AGENT_PREFETCH_MB_PER_TICK, default 32 MiB).
+pub fn budget_bytes() -> u64 {
+    std::env::var("AGENT_PREFETCH_MB_PER_TICK").ok()
+        .and_then(|v| v.parse::<u64>().ok()).unwrap_or(32) << 20
+}
+
+/// A predicted page was faulted `lead_ns` after it was requested.
+pub fn record_hit(tgid: u32, dev: u64, ino: u64, lead_ns: u64) {
+    let mut acc = ACCOUNT.lock().unwrap();
+    acc.lead.record(lead_ns / 1000);
+    acc.file(tgid, dev, ino).hits += 1;
+}
+
+/// Predicted pages expired without being faulted.
+pub fn record_waste(tgid: u32, dev: u64, ino: u64, pages: u64) {
+    ACCOUNT.lock().unwrap().file(tgid, dev, ino).waste += pages;
+}
+
+/// Close the interval: return its stats and reset the counters and the budget.
+pub fn take_interval(majflt: u64) -> PrefetchStats {
+    let mut acc = ACCOUNT.lock().unwrap();
+    let active = acc.actions > 0;
+    let ew = if active { &mut acc.majflt_active } else { &mut acc.majflt_idle };
+    *ew = 0.7 * *ew + 0.3 * majflt as f64;
+    let mut files: Vec<PrefetchFileStat> = acc.files.drain().map(|(_, f)| f).collect();
+    let hits: u64 = files.iter().map(|f| f.hits).sum();
+    let waste: u64 = files.iter().map(|f| f.waste).sum();
+    files.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+    files.truncate(TOP_FILES);
+    let out = PrefetchStats {
+        actions: acc.actions,
+        bytes_requested: acc.bytes,
+        bytes_over_budget: acc.over_budget,
+        budget_bytes: budget_bytes(),
+        hits,
+        waste,
+        hit_rate: if hits + waste > 0 { hits as f64 / (hits + waste) as f64 } else { 0.0 },
+        lead: acc.lead.summary(),
+        files,
+        majflt,
+        majflt_ewma_active: acc.majflt_active,
+        majflt_ewma_idle: acc.majflt_idle,
+    };
+    acc.actions = 0;
+    acc.bytes = 0;
+    acc.over_budget = 0;
+    acc.lead = Log2Hist::default();
+    out
+}
+
+/// File pages a prefetch actually requested, after budget trimming.
+#[derive(Debug, Clone)]
+pub struct Issued {
+    pub tgid: u32,
+    pub dev: u64,
+    pub ino: u64,
+    pub pages: Vec<u64>,
+}
+
+fn record_issued(a: &PrefetchAction) {
+    if a.dev == 0 && a.ino == 0 { return; }
+    let pages = a.ranges.iter()
+        .flat_map(|&(off, len)| off / PAGE..(off + len).div_ceil(PAGE))
+        .collect();
+    let mut issued = ISSUED.lock().unwrap();
+    if issued.len() >= MAX_ISSUED { issued.remove(0); }
+    issued.push(Issued { tgid: a.tgid, dev: a.dev, ino: a.ino, pages });
+}
+
+/// Pages issued since the last call, for hit/waste tracking.
+pub fn take_issued() -> Vec<Issued> {
+    std::mem::take(&mut *ISSUED.lock().unwrap())
+}
+
+/// Trim `ranges` to what is left of the interval budget; nothing is charged yet.
+fn fit(a: &PrefetchAction) -> Vec<(u64,u64)> {
+    let budget = budget_bytes();
+    let mut acc = ACCOUNT.lock().unwrap();
+    let mut out = Vec::new();
+    let mut fitted = 0u64;
+    for &(off, len) in &a.ranges {
+        let left = budget.saturating_sub(acc.bytes + fitted);
+        let take = len.min(left);
+        if take > 0 { out.push((off, take)); }
+        fitted += take;
+        acc.over_budget += len - take;
+    }
+    out
+}
+
+/// Charge ranges that were actually requested against the interval budget.
+fn charge(a: &PrefetchAction) {
+    let bytes: u64 = a.ranges.iter().map(|r| r.1).sum();
+    if bytes == 0 { return; }
+    let mut acc = ACCOUNT.lock().unwrap();
+    acc.actions += 1;
+    acc.bytes += bytes;
+    acc.file(a.tgid, a.dev, a.ino).bytes += bytes;
+}
+
 lazy_static! {
-    static ref FD_CACHE: Mutex<HashMap<(u32,u64,u64), RawFd>> = Mutex::new(HashMap::new());
+    static ref ACCOUNT: Mutex<Account> = Mutex::new(Account::default());
+    static ref FD_CACHE: Mutex<FdCache> = Mutex::new(FdCache::new());
+    static ref ISSUED: Mutex<Vec<Issued>> = Mutex::new(Vec::new());
+    // process_madvise(MADV_WILLNEED) usable on this kernel (5.10+) with our privileges
+    static ref PMADV_OK: bool = probe_process_madvise();
 }
 
-fn resolve_fd(tgid: u32, dev: u64, ino: u64) -> Result<RawFd> {
-    let key = (tgid, dev, ino);
-    // fast path
-    if let Some(fd) = FD_CACHE.lock().unwrap().get(&key).copied() {
-        return Ok(fd);
+fn probe_process_madvise() -> bool {
+    let pidfd = match pmadv::pidfd_open(std::process::id() as i32) {
+        Ok(fd) => fd,
+        Err(e) => { eprintln!("[agent] pidfd_open unavailable: {e}"); return false; }
+    };
+    // an empty iovec is accepted by kernels that implement the syscall
+    match pmadv::process_madvise(&pidfd, &[], pmadv::MADV_WILLNEED) {
+        Ok(_) => true,
+        Err(e) => { eprintln!("[agent] process_madvise unavailable: {e}"); false }
     }
-    // Scan /proc/<tgid>/maps for matching dev:ino and open path
+}
+
+pub fn process_madvise_supported() -> bool { *PMADV_OK }
+
+impl PrefetchBackend {
+    /// AGENT_PREFETCH_BACKEND=fadvise|readahead|madvise; defaults to process_madvise when the kernel has it.
+    pub fn preferred() -> Self {
+        match std::env::var("AGENT_PREFETCH_BACKEND").unwrap_or_default().to_ascii_lowercase().as_str() {
+            "fadvise" => PrefetchBackend::Fadvise,
+            "readahead" => PrefetchBackend::Readahead,
+            "madvise" => PrefetchBackend::ProcessMadvise,
+            _ if process_madvise_supported() => PrefetchBackend::ProcessMadvise,
+            _ => PrefetchBackend::Fadvise,
+        }
+    }
+}
+
+/// One file-backed line of /proc/<tgid>/maps.
+/// `dev` is (major, minor).
+struct MapsEntry { start: u64, end: u64, off: u64, dev: (u64, u64), ino: u64, path: String }
+
+fn maps_entries(tgid: u32) -> Result<Vec<MapsEntry>> {
     let maps = std::fs::read_to_string(format!("/proc/{}/maps", tgid))
         .with_context(|| format!("read /proc/{}/maps", tgid))?;
+    let mut out = Vec::new();
     for line in maps.lines() {
         // sample: 7f9f... r--p 00000000 08:01 131339 /lib/x.so
         // fields: addr perms offset dev inode pathname
         let mut parts = line.split_whitespace();
-        let _addr = parts.next();
+        let addr = parts.next();
         let _perms = parts.next();
-        let _off = parts.next();
+        let off = parts.next();
         let dev_field = parts.next();
         let ino_field = parts.next();
         let path = parts.collect::<Vec<_>>().join(" ");
-        if dev_field.is_none() || ino_field.is_none() || path.is_empty() { continue; }
-        let dev_s = dev_field.unwrap();
-        let ino_s = ino_field.unwrap();
-        let mut dm = dev_s.split(':');
-        let maj = u64::from_str_radix(dm.next().unwrap_or("0"),16).unwrap_or(0);
-        let min = u64::from_str_radix(dm.next().unwrap_or("0"),16).unwrap_or(0);
-        let dev_num = (maj << 20) | min;
-        let ino_num = ino_s.parse::<u64>().unwrap_or(0);
-        if dev_num == dev && ino_num == ino {
-            // open
-            if !path.is_empty() && path.starts_with('/') {
-                let f = File::open(&path).with_context(|| format!("open {}", path))?;
-                let fd = f.as_raw_fd();
-                // leak the file descriptor intentionally, keep it in cache
-                std::mem::forget(f);
-                FD_CACHE.lock().unwrap().insert(key, fd);
-                return Ok(fd);
-            }
+        let (Some(addr), Some(off), Some(dev_s), Some(ino_s)) = (addr, off, dev_field, ino_field) else { continue };
+        let Some((a, b)) = addr.split_once('-') else { continue };
+        out.push(MapsEntry {
+            start: u64::from_str_radix(a, 16).unwrap_or(0),
+            end: u64::from_str_radix(b, 16).unwrap_or(0),
+            off: u64::from_str_radix(off, 16).unwrap_or(0),
+            dev: devt::parse_hex_majmin(dev_s).unwrap_or((0, 0)),
+            ino: ino_s.parse::<u64>().unwrap_or(0),
+            path,
+        });
+    }
+    Ok(out)
+}
+
+/// Whether an open file is still the inode BPF reported (paths can be replaced or renamed).
+fn same_file(f: &File, dev: u64, ino: u64) -> bool {
+    match f.metadata() {
+        Ok(md) => md.ino() == ino && devt::user_majmin(md.dev()) == devt::kernel_majmin(dev),
+        Err(_) => false,
+    }
+}
+
+/// Drop cached descriptors of an exited process.
+pub fn evict_tgid(tgid: u32) {
+    FD_CACHE.lock().unwrap().map.retain(|k, _| k.0 != tgid);
+}
+
+fn resolve_fd(tgid: u32, dev: u64, ino: u64) -> Result<File> {
+    let key = (tgid, dev, ino);
+    // fast path, re-checked: the cached fd may point at an unlinked or replaced file
+    {
+        let mut cache = FD_CACHE.lock().unwrap();
+        if let Some(fd) = cache.get(&key) {
+            let f = File::from(fd.try_clone().context("dup cached fd")?);
+            if same_file(&f, dev, ino) { return Ok(f); }
+            cache.map.remove(&key);
         }
     }
+    // Scan /proc/<tgid>/maps for matching dev:ino. map_files opens the mapped file itself, even
+    // unlinked ("(deleted)") or outside our mount namespace, but needs CAP_CHECKPOINT_RESTORE;
+    // else resolve the path in the target's root so containerized targets hit the right file.
+    let want = devt::kernel_majmin(dev);
+    for m in maps_entries(tgid)? {
+        if m.dev != want || m.ino != ino { continue; }
+        let mapped = format!("/proc/{}/map_files/{:x}-{:x}", tgid, m.start, m.end);
+        let Ok(f) = File::open(&mapped).or_else(|_| File::open(format!("/proc/{}/root{}", tgid, m.path))) else { continue };
+        if !same_file(&f, dev, ino) { continue; }
+        FD_CACHE.lock().unwrap().insert(key, OwnedFd::from(f.try_clone()?));
+        return Ok(f);
+    }
     anyhow::bail!("failed to resolve fd for tgid={} dev={} ino={}", tgid, dev, ino);
 }
 
+/// Page-cache residency of `npages` pages of dev:ino from page `first`, cut short at EOF;
+/// None when the file can't be resolved or read.
+pub fn residency(tgid: u32, dev: u64, ino: u64, first: u64, npages: u64) -> Option<Vec<bool>> {
+    let f = resolve_fd(tgid, dev, ino).ok()?;
+    crate::pagecache::mincore_pages(&f, first * PAGE, npages * PAGE).ok()
+}
+
+/// (file offset, len) and the (address, len) it is mapped at.
+type MappedRange = ((u64,u64), (u64,u64));
+
+/// Translate file ranges into the target's virtual addresses through its mappings of dev:ino.
+/// Parts of a range that are not mapped are dropped.
+fn file_ranges_to_addrs(tgid: u32, dev: u64, ino: u64, ranges: &[(u64,u64)]) -> Result<Vec<MappedRange>> {
+    let want = devt::kernel_majmin(dev);
+    let maps: Vec<MapsEntry> = maps_entries(tgid)?.into_iter()
+        .filter(|m| m.dev == want && m.ino == ino)
+        .collect();
+    let mut out = Vec::new();
+    for &(off, len) in ranges {
+        for m in &maps {
+            let map_end = m.off + (m.end - m.start);
+            let lo = off.max(m.off);
+            let hi = (off + len).min(map_end);
+            if hi > lo { out.push(((lo, hi - lo), (m.start + (lo - m.off), hi - lo))); }
+        }
+    }
+    Ok(out)
+}
+
+/// Returns the ranges of `a` that were requested: only the mapped parts of file ranges.
+fn exec_process_madvise(a: &PrefetchAction) -> Result<Vec<(u64,u64)>> {
+    let (ranges, addrs): (Vec<_>, Vec<_>) = if a.dev == 0 && a.ino == 0 {
+        a.ranges.iter().map(|&r| (r, r)).unzip()
+    } else {
+        file_ranges_to_addrs(a.tgid, a.dev, a.ino, &a.ranges)?.into_iter().unzip()
+    };
+    if addrs.is_empty() { return Ok(ranges); }
+    let pidfd = pmadv::pidfd_open(a.tgid as i32).with_context(|| format!("pidfd_open {}", a.tgid))?;
+    pmadv::process_madvise(&pidfd, &addrs, pmadv::MADV_WILLNEED)
+        .with_context(|| format!("process_madvise(WILLNEED) pid {}", a.tgid))?;
+    Ok(ranges)
+}
+
+/// Issue `a` within the interval budget. Only what was actually requested is charged
+/// and tracked for hit/waste; a failed or empty request costs nothing.
 pub fn exec(a: &PrefetchAction) -> Result<()> {
-    let fd = resolve_fd(a.tgid, a.dev, a.ino)?;
+    let ranges = fit(a);
+    if ranges.is_empty() { return Ok(()); }
+    let ranges = issue(&PrefetchAction { ranges, ..a.clone() })?;
+    if ranges.is_empty() { return Ok(()); }
+    let a = &PrefetchAction { ranges, ..a.clone() };
+    charge(a);
+    record_issued(a);
+    Ok(())
+}
+
+/// Request `a` through its backend; returns the ranges actually requested.
+fn issue(a: &PrefetchAction) -> Result<Vec<(u64,u64)>> {
+    if let PrefetchBackend::ProcessMadvise = a.backend {
+        if process_madvise_supported() { return exec_process_madvise(a); }
+        // anonymous ranges have no file to fall back to
+        if a.dev == 0 && a.ino == 0 { return Ok(Vec::new()); }
+    }
+    let file = resolve_fd(a.tgid, a.dev, a.ino)?;
+    let fd = file.as_raw_fd();
     for (off, len) in &a.ranges {
         unsafe {
             match a.backend {
-                PrefetchBackend::Fadvise => {
+                PrefetchBackend::Fadvise | PrefetchBackend::ProcessMadvise => {
                     let _ = posix_fadvise(fd, *off as i64, *len as i64, PosixFadviseAdvice::POSIX_FADV_WILLNEED);
                 }
                 PrefetchBackend::Readahead => {
@@ -79,5 +390,5 @@ pub fn exec(a: &PrefetchAction) -> Result<()> {
             }
         }
     }
-    Ok(())
-}
\ No newline at end of file
+    Ok(a.ranges.clone())
+}
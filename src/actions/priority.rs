@@ -6,6 +6,7 @@ const IOPRIO_CLASS_RT: i32 = 1;
 const IOPRIO_CLASS_BE: i32 = 2;
 const IOPRIO_CLASS_IDLE: i32 = 3;
 const IOPRIO_WHO_PROCESS: i32 = 1;
+const REPORT_MAX_FAILURES: usize = 8;
 
 fn for_each_pid_in_cgroup<F>(cg: &str, mut f: F) -> Result<()>
 where
@@ -21,20 +22,105 @@ where
     Ok(())
 }
 
-/// Set nice value (-20..19) for all tasks in cgroup
-pub fn set_nice_for_cgroup(cg: &str, prio: i32) -> Result<()> {
-    let prio = prio.clamp(-20, 19);
-    unsafe {
-        for_each_pid_in_cgroup(cg, |pid| {
-            let _ = libc::setpriority(libc::PRIO_PROCESS, pid as u32, prio);
-        })?;
+/// Thread ids of `cg`: cgroup.threads when present (also right for threaded cgroups),
+/// else every /proc/<pid>/task entry of cgroup.procs.
+pub(crate) fn threads_of_cgroup(cg: &str) -> Result<Vec<i32>> {
+    if let Ok(data) = fs::read_to_string(format!("{}/cgroup.threads", cg)) {
+        return Ok(data.lines().filter_map(|l| l.trim().parse::<i32>().ok()).collect());
     }
-    Ok(())
+    let mut out = Vec::new();
+    for_each_pid_in_cgroup(cg, |pid| {
+        let Ok(rd) = fs::read_dir(format!("/proc/{}/task", pid)) else { return };
+        for e in rd.flatten() {
+            if let Some(tid) = e.file_name().to_str().and_then(|s| s.parse::<i32>().ok()) {
+                out.push(tid);
+            }
+        }
+    })?;
+    Ok(out)
+}
+
+pub(crate) fn comm_of(tid: i32) -> String {
+    fs::read_to_string(format!("/proc/{}/comm", tid)).map(|s| s.trim().to_string()).unwrap_or_default()
+}
+
+// comm is cut to TASK_COMM_LEN - 1 bytes
+const COMM_MAX: usize = 15;
+
+/// Shell-style match of a thread name: `*` any run, `?` one character. A comm of the full
+/// 15 bytes may have been truncated, so a longer pattern matches it on its prefix.
+pub fn comm_matches(pattern: &str, comm: &str) -> bool {
+    fn go(p: &[u8], s: &[u8], cut: bool) -> bool {
+        match (p.first(), s.first()) {
+            (None, None) => true,
+            (Some(_), None) if cut => true,
+            (Some(b'*'), _) => go(&p[1..], s, cut) || (!s.is_empty() && go(p, &s[1..], cut)),
+            (Some(b'?'), Some(_)) => go(&p[1..], &s[1..], cut),
+            (Some(a), Some(b)) if a == b => go(&p[1..], &s[1..], cut),
+            _ => false,
+        }
+    }
+    go(pattern.as_bytes(), comm.as_bytes(), comm.len() >= COMM_MAX)
 }
 
-/// Set I/O priority for all tasks in cgroup.
+/// Outcome of applying one setting to the threads of a cgroup.
+#[derive(Debug, Default)]
+pub struct ThreadReport {
+    pub what: String,
+    pub applied: usize,
+    pub skipped: usize, // filtered out by comm or exited meanwhile
+    pub failed: Vec<(i32, String, String)>, // (tid, comm, error)
+}
+
+impl ThreadReport {
+    pub fn log(&self, cg: &str) {
+        if self.failed.is_empty() { return; }
+        eprintln!("[agent] {} on {}: {} applied, {} skipped, {} failed",
+            self.what, cg, self.applied, self.skipped, self.failed.len());
+        for (tid, comm, e) in self.failed.iter().take(REPORT_MAX_FAILURES) {
+            eprintln!("[agent]   tid {} ({}): {}", tid, comm, e);
+        }
+    }
+}
+
+/// Apply `f` to each thread of `cg` whose comm matches `comm` (all when None).
+pub(crate) fn apply_per_thread<F>(cg: &str, comm: Option<&str>, what: &str, mut f: F) -> Result<ThreadReport>
+where
+    F: FnMut(i32) -> std::io::Result<()>,
+{
+    let mut rep = ThreadReport { what: what.to_string(), ..Default::default() };
+    for tid in threads_of_cgroup(cg)? {
+        let name = comm_of(tid);
+        if let Some(p) = comm {
+            if !comm_matches(p, &name) { rep.skipped += 1; continue; }
+        }
+        match f(tid) {
+            Ok(()) => rep.applied += 1,
+            // thread exited between listing and applying
+            Err(e) if e.raw_os_error() == Some(libc::ESRCH) || e.kind() == std::io::ErrorKind::NotFound => rep.skipped += 1,
+            Err(e) => rep.failed.push((tid, name, e.to_string())),
+        }
+    }
+    rep.log(cg);
+    Ok(rep)
+}
+
+fn errno(rc: libc::c_long) -> std::io::Result<()> {
+    if rc < 0 { Err(std::io::Error::last_os_error()) } else { Ok(()) }
+}
+
+/// Set nice value (-20..19) for every thread in cgroup
+pub fn set_nice_for_cgroup(cg: &str, prio: i32, comm: Option<&str>) -> Result<ThreadReport> {
+    let prio = prio.clamp(-20, 19);
+    apply_per_thread(cg, comm, "setpriority", |tid| unsafe {
+        // on Linux PRIO_PROCESS with a tid affects just that thread
+        errno(libc::setpriority(libc::PRIO_PROCESS, tid as u32, prio) as libc::c_long)
+    })
+}
+
+/// Set I/O priority for every thread in cgroup.
 /// class: 1=RT, 2=BE, 3=IDLE. prio: 0..7 (0 highest) for RT/BE; ignored for IDLE.
-pub fn set_ioprio_for_cgroup(cg: &str, class: i32, prio: i32) -> Result<()> {
+pub fn set_ioprio_for_cgroup(cg: &str, class: i32, prio: i32, comm: Option<&str>) -> Result<ThreadReport> {
     let class = match class {
         1 => IOPRIO_CLASS_RT,
         2 => IOPRIO_CLASS_BE,
@@ -43,24 +129,55 @@ pub fn set_ioprio_for_cgroup(cg: &str, class: i32, prio: i32) -> Result<()> {
     };
     let prio = prio.clamp(0, 7);
     let ioprio = ((class & 0x3) << 13) | (prio & 0x7);
-    unsafe {
-        for_each_pid_in_cgroup(cg, |pid| {
-            let _ = libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, pid, ioprio);
-        })?;
-    }
-    Ok(())
+    apply_per_thread(cg, comm, "ioprio_set", |tid| unsafe {
+        errno(libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, tid, ioprio))
+    })
 }
 
-/// Toggle SCHED_BATCH for all tasks (0 priority).
-pub fn set_sched_batch_for_cgroup(cg: &str, enable: bool) -> Result<()> {
-    unsafe {
-        let mut param = libc::sched_param {
-            sched_priority: 0,
-        };
-        let policy = if enable { libc::SCHED_BATCH } else { libc::SCHED_OTHER };
-        for_each_pid_in_cgroup(cg, |pid| {
-            let _ = libc::sched_setscheduler(pid, policy, &mut param as *mut _);
-        })?;
+/// Toggle SCHED_BATCH for every thread (0 priority).
+pub fn set_sched_batch_for_cgroup(cg: &str, enable: bool, comm: Option<&str>) -> Result<ThreadReport> {
+    let param = libc::sched_param { sched_priority: 0 };
+    let policy = if enable { libc::SCHED_BATCH } else { libc::SCHED_OTHER };
+    apply_per_thread(cg, comm, "sched_setscheduler", |tid| unsafe {
+        errno(libc::sched_setscheduler(tid, policy, &param) as libc::c_long)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::comm_matches;
+
+    #[test]
+    fn exact_and_wildcards() {
+        assert!(comm_matches("nginx", "nginx"));
+        assert!(!comm_matches("nginx", "nginx2"));
+        assert!(!comm_matches("nginx2", "nginx"));
+        assert!(comm_matches("*", "anything"));
+        assert!(comm_matches("*", ""));
+        assert!(comm_matches("", ""));
+        assert!(!comm_matches("", "x"));
+        assert!(comm_matches("java?", "java1"));
+        assert!(!comm_matches("java?", "java"));
+        assert!(comm_matches("*-io-*", "rocksdb-io-3"));
     }
-    Ok(())
-}
\ No newline at end of file
+
+    #[test]
+    fn prefix_patterns() {
+        assert!(comm_matches("worker-*", "worker-"));
+        assert!(comm_matches("worker-*", "worker-17"));
+        assert!(!comm_matches("worker-*", "worker"));
+        assert!(!comm_matches("worker-*", "my-worker-1"));
+    }
+
+    #[test]
+    fn truncated_comm_matches_longer_pattern() {
+        // "postgres-walwriter" as the kernel stores it
+        let comm = "postgres-walwri";
+        assert_eq!(comm.len(), 15);
+        assert!(comm_matches("postgres-walwriter", comm));
+        assert!(comm_matches("postgres-wal*er", comm));
+        assert!(!comm_matches("postgres-autovacuum", comm));
+        // a shorter comm is complete, so the pattern has to match all of it
+        assert!(!comm_matches("postgres-walwriter", "postgres-wal"));
+    }
+}
@@ -0,0 +1,230 @@
+// src/actions/rt.rs
+use anyhow::Result;
+use lazy_static::lazy_static;
+use std::{collections::HashMap, fs, path::Path, sync::Mutex};
+use super::priority::{comm_matches, threads_of_cgroup};
+use super::sched::{sched_getattr, sched_setattr, SchedAttr};
+use crate::metrics::{Snapshot, TaskBurst};
+
+const SCHED_FIFO: u32 = 1;
+const SCHED_RR: u32 = 2;
+const SCHED_DEADLINE: u32 = 6;
+const SCHED_FLAG_RESET_ON_FORK: u64 = 0x01;
+const SCHED_ATTR_SIZE_VER1: u32 = 56;
+// floor well above the kernel's 1024 ns minimum sched_runtime, where overheads dominate
+const MIN_RUNTIME_US: u64 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RtPolicy {
+    Fifo { prio: u32 },
+    Rr { prio: u32 },
+    Deadline { runtime_us: u64, deadline_us: u64, period_us: u64 },
+}
+
+/// Opt-in switches and bounds, from AGENT_RT_*.
+#[derive(Debug, Clone)]
+pub struct RtLimits {
+    pub enabled: bool,
+    pub threads: Option<String>, // comm pattern of threads to promote
+    pub kind: String,            // fifo | rr | deadline
+    pub max_prio: u32,
+    pub max_threads: usize,
+    pub max_share: f64,
+    pub bw_fraction: f64,        // share of the system RT bandwidth our deadline threads may reserve
+}
+
+impl RtLimits {
+    pub fn from_env() -> Self {
+        let var = |k: &str| std::env::var(k).ok().filter(|v| !v.is_empty());
+        let num = |k: &str, d: f64| var(k).and_then(|v| v.parse::<f64>().ok()).unwrap_or(d);
+        let threads = var("AGENT_RT_THREADS");
+        Self {
+            enabled: threads.is_some(),
+            threads,
+            kind: var("AGENT_RT_POLICY").unwrap_or_else(|| "fifo".into()).to_ascii_lowercase(),
+            max_prio: num("AGENT_RT_MAX_PRIO", 10.0).clamp(1.0, 99.0) as u32,
+            max_threads: num("AGENT_RT_MAX_THREADS", 4.0).max(0.0) as usize,
+            max_share: num("AGENT_RT_MAX_SHARE", 0.5).clamp(0.01, 1.0),
+            bw_fraction: num("AGENT_RT_BW_FRACTION", 0.5).clamp(0.0, 1.0),
+        }
+    }
+}
+
+struct Promotion {
+    orig: SchedAttr,
+    policy: RtPolicy,
+    // max_burst_us when the watchdog first saw the thread promoted
+    burst_base: Option<u64>,
+}
+
+lazy_static! {
+    static ref PROMOTED: Mutex<HashMap<i32, Promotion>> = Mutex::new(HashMap::new());
+}
+
+fn read_i64(path: &str) -> Option<i64> {
+    fs::read_to_string(path).ok()?.trim().parse::<i64>().ok()
+}
+
+/// System RT bandwidth as (runtime_us, period_us); None when RT throttling is off (-1),
+/// which removes the kernel's safety net, or unreadable.
+fn rt_bandwidth() -> Option<(u64, u64)> {
+    let rt = read_i64("/proc/sys/kernel/sched_rt_runtime_us")?;
+    let period = read_i64("/proc/sys/kernel/sched_rt_period_us")?;
+    if rt < 0 || period <= 0 { return None; }
+    Some((rt as u64, period as u64))
+}
+
+/// With RT group scheduling the cgroup needs its own cpu.rt_runtime_us; 0 means RT tasks can't run.
+fn cgroup_rt_ok(cg: &str) -> bool {
+    let p = format!("{}/cpu.rt_runtime_us", cg);
+    if !Path::new(&p).exists() { return true; }
+    read_i64(&p).map(|v| v != 0).unwrap_or(false)
+}
+
+/// SCHED_DEADLINE parameters from observed bursts: the mean activation period becomes
+/// period and deadline, runtime covers the mean burst with 50% headroom.
+pub fn deadline_for(t: &TaskBurst, interval_ms: u64) -> Option<RtPolicy> {
+    if t.bursts == 0 { return None; }
+    let period_us = (interval_ms * 1000 / t.bursts).max(100);
+    let runtime_us = ((t.avg_burst_us * 1.5) as u64).max(MIN_RUNTIME_US).min(period_us * 9 / 10);
+    Some(RtPolicy::Deadline { runtime_us, deadline_us: period_us, period_us })
+}
+
+/// Promotions for target threads matching AGENT_RT_THREADS that are not promoted yet.
+pub fn plan(snap: &Snapshot, lim: &RtLimits) -> Vec<(i32, RtPolicy)> {
+    let Some(pat) = lim.threads.as_deref() else { return Vec::new() };
+    let promoted = PROMOTED.lock().unwrap();
+    let room = lim.max_threads.saturating_sub(promoted.len());
+    let prio = lim.max_prio;
+    snap.tasks.iter()
+        .filter(|t| comm_matches(pat, &t.comm) && !promoted.contains_key(&t.tid))
+        .filter_map(|t| match lim.kind.as_str() {
+            "rr" => Some((t.tid, RtPolicy::Rr { prio })),
+            "deadline" => deadline_for(t, snap.interval_ms).map(|p| (t.tid, p)),
+            _ => Some((t.tid, RtPolicy::Fifo { prio })),
+        })
+        .take(room)
+        .collect()
+}
+
+fn attr_for(policy: RtPolicy, lim: &RtLimits) -> SchedAttr {
+    let mut a = SchedAttr { size: SCHED_ATTR_SIZE_VER1, sched_flags: SCHED_FLAG_RESET_ON_FORK, ..Default::default() };
+    match policy {
+        RtPolicy::Fifo { prio } => { a.sched_policy = SCHED_FIFO; a.sched_priority = prio.clamp(1, lim.max_prio); }
+        RtPolicy::Rr { prio } => { a.sched_policy = SCHED_RR; a.sched_priority = prio.clamp(1, lim.max_prio); }
+        RtPolicy::Deadline { runtime_us, deadline_us, period_us } => {
+            a.sched_policy = SCHED_DEADLINE;
+            a.sched_runtime = runtime_us * 1000;
+            a.sched_deadline = deadline_us * 1000;
+            a.sched_period = period_us * 1000;
+        }
+    }
+    a
+}
+
+/// Promote `threads` of `cg`, within the opt-in limits and the system and cgroup RT bandwidth.
+pub fn promote(cg: &str, threads: &[(i32, RtPolicy)], dry: bool) -> Result<()> {
+    let lim = RtLimits::from_env();
+    if !lim.enabled {
+        eprintln!("[agent] RT promotion not enabled (--rt-threads); skipping");
+        return Ok(());
+    }
+    let Some((rt_runtime, rt_period)) = rt_bandwidth() else {
+        eprintln!("[agent] sched_rt_runtime_us is -1 or unreadable; refusing RT promotion without throttling");
+        return Ok(());
+    };
+    if !cgroup_rt_ok(cg) {
+        eprintln!("[agent] {}/cpu.rt_runtime_us is 0; grant RT bandwidth to the cgroup first", cg);
+        return Ok(());
+    }
+    let members = threads_of_cgroup(cg)?;
+    let cpus = num_cpus::get().max(1) as f64;
+    let dl_cap = lim.bw_fraction * cpus * rt_runtime as f64 / rt_period as f64;
+
+    let mut promoted = PROMOTED.lock().unwrap();
+    let mut dl_used: f64 = promoted.values().map(|p| match p.policy {
+        RtPolicy::Deadline { runtime_us, period_us, .. } => runtime_us as f64 / period_us as f64,
+        _ => 0.0,
+    }).sum();
+    for &(tid, policy) in threads {
+        if promoted.len() >= lim.max_threads { break; }
+        if promoted.contains_key(&tid) || !members.contains(&tid) { continue; }
+        if let RtPolicy::Deadline { runtime_us, period_us, .. } = policy {
+            let bw = runtime_us as f64 / period_us.max(1) as f64;
+            if dl_used + bw > dl_cap {
+                eprintln!("[agent] SCHED_DEADLINE for tid {} would exceed {:.2} CPUs of reserved bandwidth; skipping", tid, dl_cap);
+                continue;
+            }
+            dl_used += bw;
+        }
+        if dry {
+            eprintln!("[dry-run] would promote tid {} to {:?}", tid, policy);
+            continue;
+        }
+        let orig = match sched_getattr(tid) {
+            Ok(a) => a,
+            Err(e) => { eprintln!("[agent] sched_getattr tid {}: {e}", tid); continue; }
+        };
+        match sched_setattr(tid, &attr_for(policy, &lim)) {
+            Ok(()) => {
+                eprintln!("[agent] promoted tid {} to {:?}", tid, policy);
+                promoted.insert(tid, Promotion { orig, policy, burst_base: None });
+            }
+            // EBUSY: deadline admission control; EPERM: RLIMIT_RTPRIO or a restricted cpuset for DEADLINE
+            Err(e) => eprintln!("[agent] promote tid {} to {:?}: {e}", tid, policy),
+        }
+    }
+    Ok(())
+}
+
+fn restore(tid: i32, p: &Promotion) {
+    let mut a = p.orig;
+    a.size = SCHED_ATTR_SIZE_VER1;
+    if let Err(e) = sched_setattr(tid, &a) {
+        if e.raw_os_error() != Some(libc::ESRCH) {
+            eprintln!("[agent] demote tid {}: {e}", tid);
+        }
+    }
+}
+
+/// Why a promoted thread should be demoted, if it should. FIFO/RR threads are held to
+/// AGENT_RT_MAX_SHARE of a CPU. A DEADLINE thread can't exceed its reservation, since the
+/// kernel throttles it at runtime; a new longest burst that fills the runtime means it ran
+/// into that throttle, so the reservation derived from its bursts no longer fits.
+fn overrun(p: &mut Promotion, t: &TaskBurst, interval_ms: u64, lim: &RtLimits) -> Option<String> {
+    match p.policy {
+        RtPolicy::Deadline { runtime_us, .. } => {
+            let base = *p.burst_base.get_or_insert(t.max_burst_us);
+            // bursts are accounted at context switches, so a throttled one lands just under runtime
+            (t.max_burst_us > base && t.max_burst_us >= runtime_us * 9 / 10)
+                .then(|| format!("ran a {} us burst into its {} us runtime", t.max_burst_us, runtime_us))
+        }
+        RtPolicy::Fifo { .. } | RtPolicy::Rr { .. } => {
+            let share = t.oncpu_us as f64 / (interval_ms.max(1) * 1000) as f64;
+            (share > lim.max_share)
+                .then(|| format!("used {:.0}% of a CPU, budget {:.0}%", share * 100.0, lim.max_share * 100.0))
+        }
+    }
+}
+
+/// Demote promoted threads that overran their policy over the last interval,
+/// and forget threads that exited.
+pub fn watchdog(snap: &Snapshot) {
+    let lim = RtLimits::from_env();
+    let mut promoted = PROMOTED.lock().unwrap();
+    promoted.retain(|&tid, p| {
+        if !Path::new(&format!("/proc/{}", tid)).exists() { return false; }
+        let Some(t) = snap.tasks.iter().find(|t| t.tid == tid) else { return true };
+        let Some(why) = overrun(p, t, snap.interval_ms, &lim) else { return true };
+        eprintln!("[agent] watchdog: tid {} ({}) {} under {:?}; demoting", tid, t.comm, why, p.policy);
+        restore(tid, p);
+        false
+    });
+}
+
+/// Return every promoted thread to its original policy (shutdown).
+pub fn demote_all() {
+    for (tid, p) in PROMOTED.lock().unwrap().drain() {
+        restore(tid, &p);
+    }
+}
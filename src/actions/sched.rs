@@ -0,0 +1,119 @@
+// src/actions/sched.rs
+use anyhow::Result;
+use std::{fs, path::Path};
+use super::priority::apply_per_thread;
+
+// include/uapi/linux/sched.h
+const SCHED_FLAG_KEEP_POLICY: u64 = 0x08;
+const SCHED_FLAG_KEEP_PARAMS: u64 = 0x10;
+const SCHED_FLAG_UTIL_CLAMP_MIN: u64 = 0x20;
+const SCHED_FLAG_UTIL_CLAMP_MAX: u64 = 0x40;
+// latency-nice series; kernels without it reject the flag
+const SCHED_FLAG_LATENCY_NICE: u64 = 0x80;
+pub const SCHED_CAPACITY_SCALE: u32 = 1024;
+
+/// struct sched_attr, SCHED_ATTR_SIZE_VER1 plus the latency-nice field.
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct SchedAttr {
+    pub size: u32,
+    pub sched_policy: u32,
+    pub sched_flags: u64,
+    pub sched_nice: i32,
+    pub sched_priority: u32,
+    pub sched_runtime: u64,
+    pub sched_deadline: u64,
+    pub sched_period: u64,
+    pub sched_util_min: u32,
+    pub sched_util_max: u32,
+    pub sched_latency_nice: i32,
+}
+
+const SCHED_ATTR_SIZE_VER1: u32 = 56;
+const SCHED_ATTR_SIZE_VER2: u32 = 60;
+
+pub fn sched_setattr(tid: i32, attr: &SchedAttr) -> std::io::Result<()> {
+    let rc = unsafe { libc::syscall(libc::SYS_sched_setattr, tid, attr as *const SchedAttr, 0u32) };
+    if rc < 0 { Err(std::io::Error::last_os_error()) } else { Ok(()) }
+}
+
+pub fn sched_getattr(tid: i32) -> std::io::Result<SchedAttr> {
+    let mut attr = SchedAttr::default();
+    let rc = unsafe {
+        libc::syscall(libc::SYS_sched_getattr, tid, &mut attr as *mut SchedAttr, std::mem::size_of::<SchedAttr>() as u32, 0u32)
+    };
+    if rc < 0 { Err(std::io::Error::last_os_error()) } else { Ok(attr) }
+}
+
+/// /proc/<tid>/timerslack_ns for every thread; 0 restores the thread's default slack.
+pub fn set_timerslack_for_cgroup(cg: &str, ns: u64, comm: Option<&str>, dry: bool) -> Result<()> {
+    if dry {
+        eprintln!("[dry-run] would set timerslack_ns={} for threads of {}", ns, cg);
+        return Ok(());
+    }
+    apply_per_thread(cg, comm, "timerslack", |tid| {
+        let p = format!("/proc/{}/timerslack_ns", tid);
+        if !Path::new(&p).exists() { return Err(std::io::ErrorKind::NotFound.into()); }
+        fs::write(&p, ns.to_string())
+    })?;
+    Ok(())
+}
+
+/// Per-task utilization clamps (0..=1024) through sched_setattr, keeping policy and params.
+pub fn set_uclamp_for_cgroup(cg: &str, min: Option<u32>, max: Option<u32>, comm: Option<&str>, dry: bool) -> Result<()> {
+    if min.is_none() && max.is_none() { return Ok(()); }
+    let mut attr = SchedAttr {
+        size: SCHED_ATTR_SIZE_VER1,
+        sched_flags: SCHED_FLAG_KEEP_POLICY | SCHED_FLAG_KEEP_PARAMS,
+        ..Default::default()
+    };
+    if let Some(v) = min {
+        attr.sched_flags |= SCHED_FLAG_UTIL_CLAMP_MIN;
+        attr.sched_util_min = v.min(SCHED_CAPACITY_SCALE);
+    }
+    if let Some(v) = max {
+        attr.sched_flags |= SCHED_FLAG_UTIL_CLAMP_MAX;
+        attr.sched_util_max = v.min(SCHED_CAPACITY_SCALE);
+    }
+    if dry {
+        eprintln!("[dry-run] would set uclamp min={:?} max={:?} for threads of {}", min, max, cg);
+        return Ok(());
+    }
+    apply_per_thread(cg, comm, "sched_setattr(uclamp)", |tid| sched_setattr(tid, &attr))?;
+    Ok(())
+}
+
+/// Latency nice (-20..19) where the kernel carries the latency-nice extension.
+pub fn set_latency_nice_for_cgroup(cg: &str, nice: i32, comm: Option<&str>, dry: bool) -> Result<()> {
+    let attr = SchedAttr {
+        size: SCHED_ATTR_SIZE_VER2,
+        sched_flags: SCHED_FLAG_KEEP_POLICY | SCHED_FLAG_KEEP_PARAMS | SCHED_FLAG_LATENCY_NICE,
+        sched_latency_nice: nice.clamp(-20, 19),
+        ..Default::default()
+    };
+    if dry {
+        eprintln!("[dry-run] would set latency nice {} for threads of {}", attr.sched_latency_nice, cg);
+        return Ok(());
+    }
+    let rep = apply_per_thread(cg, comm, "sched_setattr(latency nice)", |tid| sched_setattr(tid, &attr))?;
+    if rep.applied == 0 && !rep.failed.is_empty() {
+        eprintln!("[agent] latency nice likely unsupported by this kernel");
+    }
+    Ok(())
+}
+
+/// Toggle SCHED_IDLE for every thread; disabling returns them to SCHED_OTHER.
+pub fn set_sched_idle_for_cgroup(cg: &str, enable: bool, comm: Option<&str>, dry: bool) -> Result<()> {
+    let policy = if enable { libc::SCHED_IDLE } else { libc::SCHED_OTHER };
+    if dry {
+        eprintln!("[dry-run] would set {} for threads of {}", if enable { "SCHED_IDLE" } else { "SCHED_OTHER" }, cg);
+        return Ok(());
+    }
+    let param = libc::sched_param { sched_priority: 0 };
+    apply_per_thread(cg, comm, "sched_setscheduler", |tid| {
+        if unsafe { libc::sched_setscheduler(tid, policy, &param) } < 0 {
+            Err(std::io::Error::last_os_error())
+        } else { Ok(()) }
+    })?;
+    Ok(())
+}
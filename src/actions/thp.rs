@@ -0,0 +1,74 @@
+// src/actions/thp.rs
+use anyhow::{Context, Result};
+use std::fs;
+use super::pmadv;
+
+const HPAGE: u64 = 2 << 20;
+
+/// Anonymous VMA from smaps with its resident footprint.
+#[derive(Debug, Clone)]
+pub struct Vma { pub start: u64, pub end: u64, pub rss_kb: u64, pub anon_huge_kb: u64 }
+
+/// Anonymous VMAs that can hold at least one huge page, hottest (largest Rss) first.
+pub fn hot_vmas(pid: i32, max: usize) -> Result<Vec<Vma>> {
+    let text = fs::read_to_string(format!("/proc/{}/smaps", pid))
+        .with_context(|| format!("read /proc/{}/smaps", pid))?;
+    let mut out = Vec::new();
+    let mut cur: Option<(Vma, bool)> = None; // (vma, anonymous mapping)
+    for line in text.lines() {
+        let mut parts = line.split_whitespace();
+        let first = parts.next().unwrap_or("");
+        if let Some((a, b)) = first.split_once('-') {
+            if let (Ok(a), Ok(b)) = (u64::from_str_radix(a, 16), u64::from_str_radix(b, 16)) {
+                if let Some((v, anon)) = cur.take() { if anon { out.push(v); } }
+                // fields: perms offset dev inode [path]; anonymous when inode is 0 and path is empty or [heap]
+                let rest: Vec<&str> = parts.collect();
+                let anon = rest.get(3) == Some(&"0") && rest.get(4).map(|p| *p == "[heap]").unwrap_or(true);
+                cur = Some((Vma { start: a, end: b, rss_kb: 0, anon_huge_kb: 0 }, anon));
+                continue;
+            }
+        }
+        let Some((v, _)) = cur.as_mut() else { continue };
+        let val = parts.next().and_then(|x| x.parse::<u64>().ok()).unwrap_or(0);
+        match first {
+            "Rss:" => v.rss_kb = val,
+            "AnonHugePages:" => v.anon_huge_kb = val,
+            _ => {}
+        }
+    }
+    if let Some((v, anon)) = cur { if anon { out.push(v); } }
+    out.retain(|v| v.end - v.start >= HPAGE && v.rss_kb > 0);
+    out.sort_by_key(|v| std::cmp::Reverse(v.rss_kb));
+    out.truncate(max);
+    Ok(out)
+}
+
+/// MADV_COLLAPSE up to `max_vmas` hot VMAs of `pid` through process_madvise.
+/// Mainline only accepts COLD, PAGEOUT, WILLNEED and COLLAPSE (6.1+) for another process, so
+/// HUGEPAGE/NOHUGEPAGE can't be set remotely; EINVAL on older kernels is reported as unsupported.
+pub fn collapse(pid: i32, max_vmas: usize, dry: bool) -> Result<()> {
+    let vmas = hot_vmas(pid, max_vmas.max(1))?;
+    if vmas.is_empty() { return Ok(()); }
+    // huge-page aligned sub-ranges; MADV_COLLAPSE rejects partial huge pages
+    let ranges: Vec<(u64, u64)> = vmas.iter().filter_map(|v| {
+        let a = (v.start + HPAGE - 1) & !(HPAGE - 1);
+        let b = v.end & !(HPAGE - 1);
+        (b > a).then(|| (a, b - a))
+    }).collect();
+    if dry {
+        eprintln!("[dry-run] would process_madvise(MADV_COLLAPSE) {} ranges of pid {}", ranges.len(), pid);
+        return Ok(());
+    }
+    let pidfd = pmadv::pidfd_open(pid).with_context(|| format!("pidfd_open {}", pid))?;
+    match pmadv::process_madvise(&pidfd, &ranges, pmadv::MADV_COLLAPSE) {
+        Ok(bytes) => {
+            eprintln!("[agent] thp collapse: {} KiB over {} ranges of pid {}", bytes >> 10, ranges.len(), pid);
+            Ok(())
+        }
+        Err(e) if e.raw_os_error() == Some(libc::EINVAL) => {
+            eprintln!("[agent] MADV_COLLAPSE via process_madvise unsupported by this kernel (needs 6.1+)");
+            Ok(())
+        }
+        Err(e) => Err(e).with_context(|| format!("process_madvise pid {}", pid)),
+    }
+}
@@ -1,7 +1,7 @@
 // src/actions/weight.rs
-use anyhow::{Context, Result};
-use std::fs;
+use anyhow::Result;
 use std::io::ErrorKind;
+use super::journal;
 
 pub fn set_weight(cg: &str, weight: u32, dry: bool) -> Result<()> {
     let w = weight.clamp(1, 10000);
@@ -10,12 +10,52 @@ pub fn set_weight(cg: &str, weight: u32, dry: bool) -> Result<()> {
         eprintln!("[dry-run] would write {} -> {}", path, w);
         return Ok(());
     }
-    match fs::write(&path, format!("{}\n", w)) {
-        Ok(_) => Ok(()),
-        Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+    match journal::write(&path, &format!("{}\n", w)) {
+        Ok(()) => Ok(()),
+        Err(e) if e.downcast_ref::<std::io::Error>().map(|e| e.kind()) == Some(ErrorKind::PermissionDenied) => {
+            journal::forget(&path);
             eprintln!("[agent] cpu.weight write denied for {}; skipping (EACCES)", path);
             Ok(())
         }
-        Err(e) => Err(e).with_context(|| format!("write {}", path)),
+        Err(e) => Err(e),
     }
-}
\ No newline at end of file
+}
+
+/// Put `cg`'s cpu.weight back to what it was before the agent first changed it.
+pub fn restore_weight(cg: &str, dry: bool) -> Result<()> {
+    let path = format!("{}/cpu.weight", cg);
+    if dry {
+        eprintln!("[dry-run] would restore {}", path);
+        return Ok(());
+    }
+    if journal::restore(&path)? { eprintln!("[agent] restored {}", path); }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn neighbour_weight_is_restored() {
+        let dir = std::env::temp_dir().join(format!("weight-cg-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let cg = dir.to_str().unwrap();
+        let path = dir.join("cpu.weight");
+        fs::write(&path, "100\n").unwrap();
+
+        set_weight(cg, 50, false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "50\n");
+        set_weight(cg, 25, false).unwrap();
+        restore_weight(cg, false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "100");
+        // restored entries leave the journal
+        assert!(!journal::restore(path.to_str().unwrap()).unwrap());
+
+        set_weight(cg, 50, false).unwrap();
+        journal::rollback();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "100");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -10,6 +10,7 @@ use std::time::Duration;
 use std::os::fd::{AsFd, AsRawFd};
 use std::mem::{size_of, MaybeUninit};
 use crate::events::{parse_comm_event, parse_tuner_event};
+use serde::Serialize;
 
 
 #[repr(C)]
@@ -27,6 +28,35 @@ mod sockops_skel { include!(concat!(env!("OUT_DIR"), "/sockops.skel.rs")); }
 mod prefetch_skel { include!(concat!(env!("OUT_DIR"), "/prefetch.skel.rs")); }
 use sockops_skel::SockopsSkelBuilder;
 
+/// Mirrors struct io_pattern in tuner.bpf.c (cumulative per tgid).
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IoPat {
+    pub last_sector: u64,
+    pub seq: u64,
+    pub rnd: u64,
+    pub bytes: u64,
+    pub reqs: u64,
+    pub lat_us_sum: u64,
+    pub dev_major: u32,
+    pub dev_minor: u32,
+}
+
+/// Mirrors struct TaskStats in common.h (cumulative per tid).
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TaskStats {
+    pub last_oncpu_ts_ns: u64,
+    pub ewma_runq_us: u64,
+    pub ewma_futex_us: u64,
+    pub page_faults: u64,
+    pub total_oncpu_us: u64,
+    pub last_cpu: u32,
+    pub _pad: u32,
+    pub nr_bursts: u64,
+    pub max_burst_us: u64,
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Default)]
 pub struct Agg { pub(crate) futex_us: u64, page_faults: u64 }
@@ -68,6 +98,189 @@ pub fn dump_target_tgids_fd(fd: i32, max: usize) -> Vec<(u32, u8)> {
     out
 }
 
+/// What the running kernel/hardware let us attach; logged at startup and carried in the Snapshot.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Capabilities {
+    pub llc_perf: bool,
+    pub llc_cpus: usize,
+    pub llc_sample_period: u64,
+    pub llc_event: String,
+    pub llc_note: Option<String>,
+    pub process_madvise: bool,
+    /// "fentry", "kprobe" or "none"
+    pub prefetch_probe: String,
+}
+
+/// Cumulative NET_STATS (struct net_stats in bpf/sockops.bpf.c) summed over CPUs, plus NET_HIST.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NetCounters {
+    pub rtt_samples: u64,
+    pub srtt_us_sum: u64,
+    pub retrans: u64,
+    pub opened_active: u64,
+    pub opened_passive: u64,
+    pub closed: u64,
+    pub connects: u64,
+    pub connect_us_sum: u64,
+    pub rtt: crate::hist::Log2Hist,
+    pub connect: crate::hist::Log2Hist,
+}
+
+/// Cumulative PREFETCH_STATS counters, summed over CPUs.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct PrefetchEventCounts {
+    pub seen: u64,
+    pub sampled_out: u64,
+    pub rate_limited: u64,
+    pub ringbuf_full: u64,
+}
+
+impl PrefetchEventCounts {
+    pub fn delta(&self, prev: &PrefetchEventCounts) -> PrefetchEventCounts {
+        PrefetchEventCounts {
+            seen: self.seen.saturating_sub(prev.seen),
+            sampled_out: self.sampled_out.saturating_sub(prev.sampled_out),
+            rate_limited: self.rate_limited.saturating_sub(prev.rate_limited),
+            ringbuf_full: self.ringbuf_full.saturating_sub(prev.ringbuf_full),
+        }
+    }
+
+    /// Events lost to the rate limit or a full ring buffer (sampling is deliberate).
+    pub fn dropped(&self) -> u64 { self.rate_limited + self.ringbuf_full }
+}
+
+fn write_sock_rules(so: &sockops_skel::SockopsSkel<'static>, rules: &[crate::sockops::SockRule]) -> Result<()> {
+    for (i, val) in crate::sockops::encode(rules).iter().enumerate() {
+        so.maps.SOCK_RULES_MAP.update(&(i as u32).to_ne_bytes(), val, MapFlags::ANY)
+            .context("write SOCK_RULES_MAP")?;
+    }
+    Ok(())
+}
+
+/// Open and load prefetch.bpf.o with only one filemap_fault program autoloaded.
+fn open_prefetch(fentry: bool) -> Result<prefetch_skel::PrefetchSkel<'static>> {
+    let obj: &'static mut MaybeUninit<libbpf_rs::OpenObject> =
+        Box::leak(Box::new(MaybeUninit::<libbpf_rs::OpenObject>::uninit()));
+    let mut open = prefetch_skel::PrefetchSkelBuilder::default()
+        .open(obj).context("open prefetch skeleton")?;
+    if fentry {
+        open.progs.on_filemap_fault.set_autoload(false);
+    } else {
+        open.progs.on_filemap_fault_fentry.set_autoload(false);
+    }
+    open.load().context("load prefetch skeleton")
+}
+
+/// AGENT_PREFETCH_PROBE=fentry|kprobe|auto (default auto: fentry, falling back to the kprobe).
+fn load_prefetch() -> Result<(prefetch_skel::PrefetchSkel<'static>, &'static str)> {
+    let mode = std::env::var("AGENT_PREFETCH_PROBE").unwrap_or_default().to_ascii_lowercase();
+    if mode != "kprobe" {
+        match open_prefetch(true) {
+            Ok(mut sk) => match sk.progs.on_filemap_fault_fentry.attach() {
+                Ok(l) => {
+                    sk.links.on_filemap_fault_fentry = Some(l);
+                    configure_prefetch(&sk)?;
+                    return Ok((sk, "fentry"));
+                }
+                Err(e) => eprintln!("[agent] fentry/filemap_fault attach failed: {e}"),
+            },
+            Err(e) => eprintln!("[agent] fentry/filemap_fault unavailable: {e:#}"),
+        }
+        if mode == "fentry" { anyhow::bail!("fentry/filemap_fault required but unavailable"); }
+    }
+    let mut sk = open_prefetch(false)?;
+    configure_prefetch(&sk)?;
+    let probe = match sk.progs.on_filemap_fault.attach() {
+        Ok(l) => { sk.links.on_filemap_fault = Some(l); "kprobe" }
+        Err(e) => { eprintln!("[agent] kprobe/filemap_fault attach failed: {e}"); "none" }
+    };
+    Ok((sk, probe))
+}
+
+/// PREFETCH_CFG from AGENT_PREFETCH_SAMPLE_SHIFT (default 0) and
+/// AGENT_PREFETCH_MAX_EVENTS (per CPU per second, default 20000, 0 = unlimited).
+fn configure_prefetch(sk: &prefetch_skel::PrefetchSkel<'static>) -> Result<()> {
+    let env_u32 = |k: &str, d: u32| std::env::var(k).ok().and_then(|v| v.parse::<u32>().ok()).unwrap_or(d);
+    let shift = env_u32("AGENT_PREFETCH_SAMPLE_SHIFT", 0).min(31);
+    let max = env_u32("AGENT_PREFETCH_MAX_EVENTS", 20_000);
+    let mut val = [0u8; 8];
+    val[0..4].copy_from_slice(&shift.to_ne_bytes());
+    val[4..8].copy_from_slice(&max.to_ne_bytes());
+    sk.maps.PREFETCH_CFG.update(&0u32.to_ne_bytes(), &val, MapFlags::ANY)
+        .context("write PREFETCH_CFG")
+}
+
+const PERF_TYPE_HARDWARE: u32 = 0;
+const PERF_TYPE_RAW: u32 = 4;
+const PERF_COUNT_HW_CACHE_MISSES: u64 = 3;
+const PERF_FLAG_FD_CLOEXEC: libc::c_ulong = 1 << 3;
+
+fn perf_event_open_cpu(typ: u32, config: u64, period: u64, cpu: i32) -> std::io::Result<i32> {
+    let mut attr: libbpf_sys::perf_event_attr = unsafe { std::mem::zeroed() };
+    attr.type_ = typ;
+    attr.size = size_of::<libbpf_sys::perf_event_attr>() as u32;
+    attr.config = config;
+    attr.__bindgen_anon_1.sample_period = period;
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_perf_event_open,
+            &attr as *const libbpf_sys::perf_event_attr,
+            -1 as libc::pid_t,
+            cpu,
+            -1 as libc::c_int,
+            PERF_FLAG_FD_CLOEXEC,
+        )
+    };
+    if fd < 0 { Err(std::io::Error::last_os_error()) } else { Ok(fd as i32) }
+}
+
+/// Open one sampling event per CPU and attach `on_llc_miss` to each.
+/// AGENT_LLC_RAW_EVENT=0x.. selects a raw PMU event instead of the generic cache-miss counter,
+/// AGENT_LLC_SAMPLE_PERIOD sets the period (default 10000).
+fn attach_llc_events(skel: &TunerSkel<'static>) -> (Vec<libbpf_rs::Link>, Capabilities) {
+    let period = std::env::var("AGENT_LLC_SAMPLE_PERIOD").ok()
+        .and_then(|v| v.parse::<u64>().ok()).unwrap_or(10_000).max(1);
+    let raw = std::env::var("AGENT_LLC_RAW_EVENT").ok()
+        .and_then(|v| u64::from_str_radix(v.trim_start_matches("0x"), 16).ok());
+    let (typ, config, label) = match raw {
+        Some(r) => (PERF_TYPE_RAW, r, format!("raw:0x{:x}", r)),
+        None => (PERF_TYPE_HARDWARE, PERF_COUNT_HW_CACHE_MISSES, "hw:cache-misses".to_string()),
+    };
+
+    let mut caps = Capabilities { llc_sample_period: period, llc_event: label, ..Default::default() };
+    let mut links = Vec::new();
+    let mut last_err: Option<std::io::Error> = None;
+    // CPU ids, not a count: offline CPUs and affinity restrictions leave gaps
+    for cpu in crate::topology::online_cpus() {
+        let fd = match perf_event_open_cpu(typ, config, period, cpu as i32) {
+            Ok(fd) => fd,
+            Err(e) => {
+                // ENODEV: offline CPU; anything else is worth reporting
+                if e.raw_os_error() != Some(libc::ENODEV) { last_err = Some(e); }
+                continue;
+            }
+        };
+        // on success the link owns the perf fd and closes it on drop
+        match skel.progs.on_llc_miss.attach_perf_event(fd) {
+            Ok(l) => links.push(l),
+            Err(e) => {
+                unsafe { libc::close(fd); }
+                caps.llc_note = Some(format!("attach on_llc_miss: {e}"));
+            }
+        }
+    }
+    caps.llc_cpus = links.len();
+    caps.llc_perf = !links.is_empty();
+    if !caps.llc_perf && caps.llc_note.is_none() {
+        caps.llc_note = Some(match last_err.as_ref().and_then(|e| e.raw_os_error()) {
+            Some(libc::ENOENT) | Some(libc::EOPNOTSUPP) => "hardware counters unavailable (VM?)".to_string(),
+            Some(libc::EACCES) | Some(libc::EPERM) => "perf_event_open denied (CAP_PERFMON / perf_event_paranoid)".to_string(),
+            _ => format!("perf_event_open failed: {:?}", last_err),
+        });
+    }
+    (links, caps)
+}
+
 pub struct AgentBpf {
     // Prefetch sensor skeleton & ringbuf
     prefetch: Option<prefetch_skel::PrefetchSkel<'static>>,
@@ -79,8 +292,15 @@ pub struct AgentBpf {
     comm_wake: Arc<AtomicU64>,
     comm_futex: Arc<AtomicU64>,
     spikes: Arc<AtomicU64>,
+    // target tgids reported exited by tp_proc_exit, drained each tick
+    exited: Arc<std::sync::Mutex<Vec<u32>>>,
     // optional sockops (kept alive to retain link)
-    _sockops: Option<sockops_skel::SockopsSkel<'static>>,
+    sockops: Option<sockops_skel::SockopsSkel<'static>>,
+    // mtime of AGENT_SOCKOPS_CONFIG when SOCK_RULES_MAP was last written
+    sockops_mtime: Option<std::time::SystemTime>,
+    // per-CPU perf event links for on_llc_miss
+    _llc_links: Vec<libbpf_rs::Link>,
+    pub caps: Capabilities,
     target_pid: i32,
  }
 
@@ -102,10 +322,7 @@ impl AgentBpf {
     pub fn load_and_attach(target_pid: i32, with_descendants: bool, follow_new: bool, attach_sockops: bool) -> Result<Self> {
         // builder.open requires MaybeUninit<OpenObject>
         let leaked: &'static mut core::mem::MaybeUninit<libbpf_rs::OpenObject> = Box::leak(Box::new(core::mem::MaybeUninit::<libbpf_rs::OpenObject>::uninit()));
-        let mut open = TunerSkelBuilder::default().open(leaked).context("open tuner skeleton")?;
-        // Disable block rq_complete autoload (kernel ctx layout varies)
-        open.progs.on_rq_complete.set_autoload(false);
-        eprintln!("[agent] on_rq_complete autoload disabled");
+        let open = TunerSkelBuilder::default().open(leaked).context("open tuner skeleton")?;
         let mut skel = open.load().context("load tuner skeleton")?;
         skel.maps.TARGET_TGIDS.pin("/sys/fs/bpf/TARGET_TGIDS")?;
 
@@ -133,6 +350,14 @@ impl AgentBpf {
             skel.links.tp_pf_user = Some(link);
         }
 
+        // page fault latency (kprobe pair, optional)
+        if let Ok(l) = skel.progs.kp_handle_mm_fault.attach() {
+            skel.links.kp_handle_mm_fault = Some(l);
+            if let Ok(l2) = skel.progs.krp_handle_mm_fault.attach() {
+                skel.links.krp_handle_mm_fault = Some(l2);
+            }
+        }
+
         // futex (enter/exit + waitv variants if present)
         if let Ok(l) = skel.progs.tp_enter_futex.attach() {
             skel.links.tp_enter_futex = Some(l);
@@ -147,9 +372,28 @@ impl AgentBpf {
             }
         }
 
+        // block I/O attribution: submitter recorded at insert/issue, matched at completion
+        match skel.progs.on_rq_complete.attach() {
+            Ok(l) => {
+                skel.links.on_rq_complete = Some(l);
+                if let Ok(l) = skel.progs.on_rq_insert.attach() { skel.links.on_rq_insert = Some(l); }
+                if let Ok(l) = skel.progs.on_rq_issue.attach() { skel.links.on_rq_issue = Some(l); }
+            }
+            Err(e) => eprintln!("[agent] block_rq tracing unavailable: {e}"),
+        }
+
         if follow_new {
             if let Ok(l) = skel.progs.tp_proc_fork.attach() { skel.links.tp_proc_fork = Some(l); }
-            if let Ok(l) = skel.progs.tp_proc_exit.attach() { skel.links.tp_proc_exit = Some(l); }
+        }
+        // exit is always tracked: it prunes TARGET_TGIDS and signals per-tgid cleanup
+        if let Ok(l) = skel.progs.tp_proc_exit.attach() { skel.links.tp_proc_exit = Some(l); }
+
+        let (llc_links, mut caps) = attach_llc_events(&skel);
+        caps.process_madvise = crate::actions::prefetch::process_madvise_supported();
+        if caps.llc_perf {
+            eprintln!("[agent] LLC sampling {} period={} on {} cpus", caps.llc_event, caps.llc_sample_period, caps.llc_cpus);
+        } else {
+            eprintln!("[agent] LLC sampling disabled: {}", caps.llc_note.as_deref().unwrap_or("unknown"));
         }
 
         // ring buffers (COMM_EVENTS, EVENTS)
@@ -171,12 +415,17 @@ impl AgentBpf {
                 0
             })?;
         }
+        let exited: Arc<std::sync::Mutex<Vec<u32>>> = Arc::new(std::sync::Mutex::new(Vec::new()));
         {
             let s = Arc::clone(&spikes);
+            let x = Arc::clone(&exited);
             rb.add(&skel.maps.EVENTS, move |data: &[u8]| -> i32 {
-                if parse_tuner_event(data).is_some() {
-                    eprintln!("EVENTS");
-                    s.fetch_add(1, Relaxed);
+                match parse_tuner_event(data) {
+                    Some(ev) if ev.kind == crate::events::EVT_PROC_EXIT => {
+                        if let Ok(mut v) = x.lock() { v.push(ev.pid); }
+                    }
+                    Some(_) => { s.fetch_add(1, Relaxed); }
+                    None => {}
                 }
                 0
             })?;
@@ -188,11 +437,15 @@ impl AgentBpf {
             let leaked: &'static mut core::mem::MaybeUninit<libbpf_rs::OpenObject> = Box::leak(Box::new(core::mem::MaybeUninit::<libbpf_rs::OpenObject>::uninit()));
             let open = SockopsSkelBuilder::default().open(leaked)?;
             let mut so = open.load()?;
-            if let Ok(file) = std::fs::File::open("/sys/fs/cgroup") {
-                let fd = file.as_raw_fd();
-                if let Ok(link) = so.progs.sockops_prog.attach_cgroup(fd) {
-                    so.links.sockops_prog = Some(link);
-                }
+            write_sock_rules(&so, &crate::sockops::load_rules()?)?;
+            // scoped to the target's cgroup (and its children), not the whole host
+            let cg = crate::cgroups::cgv2_path_of_pid(target_pid);
+            match std::fs::File::open(&cg) {
+                Ok(file) => match so.progs.sockops_prog.attach_cgroup(file.as_raw_fd()) {
+                    Ok(link) => { so.links.sockops_prog = Some(link); }
+                    Err(e) => eprintln!("[agent] attach sockops to {}: {e}", cg),
+                },
+                Err(e) => eprintln!("[agent] open {}: {e}", cg),
             }
             sock_skel = Some(so);
         }
@@ -200,14 +453,9 @@ impl AgentBpf {
         let prefetch_buf: std::sync::Arc<std::sync::Mutex<Vec<PrefetchEvt>>> =
             std::sync::Arc::new(std::sync::Mutex::new(Vec::with_capacity(4096)));
 
-        let leaked_pref: &'static mut core::mem::MaybeUninit<libbpf_rs::OpenObject> =
-            Box::leak(Box::new(core::mem::MaybeUninit::<libbpf_rs::OpenObject>::uninit()));
-        let pref_open = prefetch_skel::PrefetchSkelBuilder::default()
-            .open(leaked_pref).context("open prefetch skeleton")?;
-        let mut prefetch_skel = pref_open.load().context("load prefetch skeleton")?;
-        if let Ok(link) = prefetch_skel.progs.on_filemap_fault.attach() {
-            prefetch_skel.links.on_filemap_fault = Some(link);
-        }
+        let (prefetch_skel, probe) = load_prefetch()?;
+        caps.prefetch_probe = probe.to_string();
+        eprintln!("[agent] filemap_fault via {}", probe);
         let mut prefetch_rb_builder = RingBufferBuilder::new();
         {
             let buf = std::sync::Arc::clone(&prefetch_buf);
@@ -230,7 +478,11 @@ impl AgentBpf {
             comm_wake,
             comm_futex,
             spikes,
-            _sockops: sock_skel,
+            exited,
+            sockops: sock_skel,
+            sockops_mtime: crate::sockops::config_mtime(),
+            _llc_links: llc_links,
+            caps,
             // NEW:
             prefetch: Some(prefetch_skel),
             prefetch_rb,
@@ -251,14 +503,33 @@ impl AgentBpf {
     pub fn read_comm_futex(&self) -> u64 { self.comm_futex.load(Relaxed) }
     pub fn read_spikes(&self) -> u64 { self.spikes.load(Relaxed) }
 
+    /// Cumulative estimated LLC misses for a tgid (samples summed over CPUs x sample period).
     pub fn read_llc_for_pid(&self, tgid: u32) -> u64 {
         let map = &self.skel.maps.LLC_MISS;
         let key = tgid.to_ne_bytes();
-        if let Ok(Some(val)) = map.lookup(&key, libbpf_rs::MapFlags::ANY) {
-            if val.len() >= 8 { return u64::from_ne_bytes(val[0..8].try_into().unwrap()); }
+        let mut samples = 0u64;
+        if let Ok(Some(vals)) = map.lookup_percpu(&key, libbpf_rs::MapFlags::ANY) {
+            for v in &vals {
+                if v.len() >= 8 { samples += u64::from_ne_bytes(v[0..8].try_into().unwrap()); }
+            }
+        }
+        samples.saturating_mul(self.caps.llc_sample_period)
+    }
+    pub fn read_hist_for_tgid(&self, tgid: u32, kind: u32) -> crate::hist::Log2Hist {
+        let mut key = [0u8; 8];
+        key[0..4].copy_from_slice(&tgid.to_ne_bytes());
+        key[4..8].copy_from_slice(&kind.to_ne_bytes());
+        match self.skel.maps.LAT_HIST.lookup(&key, MapFlags::ANY) {
+            Ok(Some(val)) => crate::hist::Log2Hist::from_bytes(&val),
+            _ => crate::hist::Log2Hist::default(),
         }
-        0
     }
+
+    pub fn target_tgids(&self) -> Vec<u32> {
+        let fd = self.skel.maps.TARGET_TGIDS.as_fd().as_raw_fd();
+        dump_target_tgids_fd(fd, 8192).into_iter().map(|(t, _)| t).collect()
+    }
+
     pub fn target_tgids_fd(&self) -> Option<i32> {
         Some(self.skel.maps.TARGET_TGIDS.as_fd().as_raw_fd())
     }
@@ -290,21 +561,87 @@ impl AgentBpf {
         sum
     }
 
-    pub fn read_io_pattern_for_pid(&self, tgid: u32) -> (u64,u64) {
+    pub fn read_task_stats(&self, tid: u32) -> Option<TaskStats> {
+        let val = self.skel.maps.TID_STATS.lookup(&tid.to_ne_bytes(), libbpf_rs::MapFlags::ANY).ok()??;
+        if val.len() < size_of::<TaskStats>() { return None; }
+        // SAFETY: TaskStats mirrors struct TaskStats
+        Some(unsafe { core::ptr::read_unaligned(val.as_ptr() as *const TaskStats) })
+    }
+
+    pub fn read_io_pattern_for_pid(&self, tgid: u32) -> Option<IoPat> {
         let map = &self.skel.maps.IO_PAT;
         let key = tgid.to_ne_bytes();
-        if let Ok(Some(val)) = map.lookup(&key, libbpf_rs::MapFlags::ANY) {
-            if val.len() >= 24 {
-                let seq = u64::from_ne_bytes(val[8..16].try_into().unwrap());
-                let rnd = u64::from_ne_bytes(val[16..24].try_into().unwrap());
-                return (seq, rnd);
-            }
-        }
-        (0,0)
+        let val = map.lookup(&key, libbpf_rs::MapFlags::ANY).ok()??;
+        if val.len() < size_of::<IoPat>() { return None; }
+        // SAFETY: IoPat mirrors struct io_pattern
+        Some(unsafe { core::ptr::read_unaligned(val.as_ptr() as *const IoPat) })
     }
 }
 
 impl AgentBpf {
+     pub fn drain_exited(&self) -> Vec<u32> {
+         std::mem::take(&mut *self.exited.lock().unwrap())
+     }
+
+     /// Replace the sockops rules, e.g. from a strategy.
+     pub fn set_sock_rules(&self, rules: &[crate::sockops::SockRule]) -> Result<()> {
+         match self.sockops.as_ref() {
+             Some(so) => write_sock_rules(so, rules),
+             None => Ok(()),
+         }
+     }
+
+     /// Re-read AGENT_SOCKOPS_CONFIG when it changed on disk; a bad file keeps the old rules.
+     pub fn refresh_sock_rules(&mut self) {
+         if self.sockops.is_none() { return; }
+         let m = crate::sockops::config_mtime();
+         if m.is_none() || m == self.sockops_mtime { return; }
+         self.sockops_mtime = m;
+         match crate::sockops::load_rules().and_then(|r| self.set_sock_rules(&r)) {
+             Ok(()) => eprintln!("[agent] sockops rules reloaded"),
+             Err(e) => eprintln!("[agent] sockops reload failed: {e:#}"),
+         }
+     }
+
+     /// None when sockops is not attached.
+     pub fn read_net(&self) -> Option<NetCounters> {
+         let so = self.sockops.as_ref()?;
+         let key = 0u32.to_ne_bytes();
+         let mut c = NetCounters::default();
+         if let Ok(Some(vals)) = so.maps.NET_STATS.lookup_percpu(&key, MapFlags::ANY) {
+             for v in &vals {
+                 let f = |i: usize| v.get(i * 8..i * 8 + 8).map(|b| u64::from_ne_bytes(b.try_into().unwrap())).unwrap_or(0);
+                 c.rtt_samples += f(0);
+                 c.srtt_us_sum += f(1);
+                 c.retrans += f(2);
+                 c.opened_active += f(3);
+                 c.opened_passive += f(4);
+                 c.closed += f(5);
+                 c.connects += f(6);
+                 c.connect_us_sum += f(7);
+             }
+         }
+         for (idx, h) in [(0u32, &mut c.rtt), (1u32, &mut c.connect)] {
+             if let Ok(Some(vals)) = so.maps.NET_HIST.lookup_percpu(&idx.to_ne_bytes(), MapFlags::ANY) {
+                 for v in &vals { h.add(&crate::hist::Log2Hist::from_bytes(v)); }
+             }
+         }
+         Some(c)
+     }
+
+     pub fn read_prefetch_counts(&self) -> PrefetchEventCounts {
+         let mut c = [0u64; 4];
+         let Some(sk) = self.prefetch.as_ref() else { return PrefetchEventCounts::default() };
+         for (i, slot) in c.iter_mut().enumerate() {
+             if let Ok(Some(vals)) = sk.maps.PREFETCH_STATS.lookup_percpu(&(i as u32).to_ne_bytes(), MapFlags::ANY) {
+                 for v in &vals {
+                     if v.len() >= 8 { *slot += u64::from_ne_bytes(v[0..8].try_into().unwrap()); }
+                 }
+             }
+         }
+         PrefetchEventCounts { seen: c[0], sampled_out: c[1], rate_limited: c[2], ringbuf_full: c[3] }
+     }
+
      pub fn drain_prefetch_events(&self) -> Vec<PrefetchEvt> {
          let mut guard = self.prefetch_buf.lock().unwrap();
          let out = guard.clone();
@@ -1,6 +1,58 @@
 use anyhow::{Context, Result};
+use lazy_static::lazy_static;
 use std::{fs, io::Write, path::{Path, PathBuf}};
 
+lazy_static! {
+    // (allow, deny) from AGENT_CG_ALLOW / AGENT_CG_DENY, comma-separated
+    static ref TOUCH_POLICY: (Vec<String>, Vec<String>) = {
+        let list = |k: &str| std::env::var(k).unwrap_or_default()
+            .split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+            .collect::<Vec<_>>();
+        (list("AGENT_CG_ALLOW"), deny_list(list("AGENT_CG_DENY"), &cgv2_path_of_pid(std::process::id() as i32)))
+    };
+}
+
+/// The configured deny list plus init.scope and the agent's own cgroup. An agent in the root
+/// cgroup (or a private cgroup namespace, "0::/") adds nothing: the root would deny every
+/// path, and `may_touch` refuses the root itself anyway.
+fn deny_list(mut deny: Vec<String>, self_cg: &str) -> Vec<String> {
+    deny.push("/sys/fs/cgroup/init.scope".to_string());
+    if self_cg.trim_end_matches('/') != "/sys/fs/cgroup" { deny.push(self_cg.to_string()); }
+    deny
+}
+
+/// `pat` matches `path` exactly, as an ancestor, or as a prefix when it ends in '*'.
+fn cg_match(pat: &str, path: &str) -> bool {
+    if let Some(prefix) = pat.strip_suffix('*') { return path.starts_with(prefix); }
+    Path::new(path).starts_with(pat)
+}
+
+/// Whether the agent may write control files of `path`. The root cgroup is never touched;
+/// an empty allow list means everything not denied.
+pub fn may_touch(path: &str) -> bool {
+    let (allow, deny) = &*TOUCH_POLICY;
+    allowed(path, allow, deny)
+}
+
+fn allowed(path: &str, allow: &[String], deny: &[String]) -> bool {
+    let path = path.trim_end_matches('/');
+    if path == "/sys/fs/cgroup" { return false; }
+    if deny.iter().any(|d| cg_match(d, path)) { return false; }
+    allow.is_empty() || allow.iter().any(|a| cg_match(a, path))
+}
+
+pub fn cgv2_path_of_pid(pid: i32) -> String {
+    let cgfile = format!("/proc/{}/cgroup", pid);
+    if let Ok(s) = fs::read_to_string(&cgfile) {
+        for line in s.lines() {
+            if let Some(rest) = line.splitn(3, ':').nth(2) {
+                return format!("/sys/fs/cgroup{}", rest.trim());
+            }
+        }
+    }
+    "/sys/fs/cgroup".to_string()
+}
+
 pub fn ensure_unified() -> Result<bool> {
     Ok(Path::new("/sys/fs/cgroup/cgroup.controllers").exists())
 }
@@ -17,7 +69,7 @@ fn write_if_exists(path: &Path, data: &str) -> Result<()> {
     Ok(())
 }
 
-fn enable_controllers(parent: &Path, ctrls: &[&str]) -> Result<()> {
+pub(crate) fn enable_controllers(parent: &Path, ctrls: &[&str]) -> Result<()> {
     let supported = fs::read_to_string("/sys/fs/cgroup/cgroup.controllers")
         .unwrap_or_default();
     let want: Vec<&str> = ctrls.iter().copied()
@@ -60,4 +112,60 @@ pub fn create_and_attach(cg_path: &str, pid: i32) -> Result<()> {
         .with_context(|| format!("attach pid {} to {}", pid, cg.display()))?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Controllers usable inside `cg` (what its parent delegated).
+pub fn controller_available(cg: &Path, ctrl: &str) -> bool {
+    fs::read_to_string(cg.join("cgroup.controllers"))
+        .map(|s| s.split_whitespace().any(|c| c == ctrl))
+        .unwrap_or(false)
+}
+
+/// Make `ctrl` available in `cg` by enabling it in every ancestor's subtree_control.
+pub fn ensure_controller(cg: &Path, ctrl: &str) -> Result<bool> {
+    if controller_available(cg, ctrl) { return Ok(true); }
+    let root = Path::new("/sys/fs/cgroup");
+    let rel = cg.strip_prefix(root).unwrap_or(Path::new(""));
+    let mut cur = root.to_path_buf();
+    for comp in rel.components() {
+        enable_controllers(&cur, &[ctrl])?;
+        cur.push(comp);
+    }
+    Ok(controller_available(cg, ctrl))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cg_match_ancestor_and_prefix() {
+        assert!(cg_match("/sys/fs/cgroup/app.slice", "/sys/fs/cgroup/app.slice"));
+        assert!(cg_match("/sys/fs/cgroup/app.slice", "/sys/fs/cgroup/app.slice/db.service"));
+        assert!(!cg_match("/sys/fs/cgroup/app.slice", "/sys/fs/cgroup/app.slice2"));
+        assert!(cg_match("/sys/fs/cgroup/app*", "/sys/fs/cgroup/app.slice2"));
+        assert!(!cg_match("/sys/fs/cgroup/app*", "/sys/fs/cgroup/system.slice"));
+    }
+
+    #[test]
+    fn agent_in_root_cgroup_denies_nothing_extra() {
+        for self_cg in ["/sys/fs/cgroup/", "/sys/fs/cgroup"] {
+            let deny = deny_list(Vec::new(), self_cg);
+            assert_eq!(deny, vec!["/sys/fs/cgroup/init.scope".to_string()]);
+            assert!(allowed("/sys/fs/cgroup/app.slice/db.service", &[], &deny));
+            assert!(!allowed("/sys/fs/cgroup/init.scope", &[], &deny));
+            assert!(!allowed("/sys/fs/cgroup/", &[], &deny));
+        }
+    }
+
+    #[test]
+    fn agent_cgroup_and_lists() {
+        let deny = deny_list(vec!["/sys/fs/cgroup/system.slice".into()], "/sys/fs/cgroup/agent.slice/agent.service");
+        assert!(!allowed("/sys/fs/cgroup/agent.slice/agent.service", &[], &deny));
+        assert!(!allowed("/sys/fs/cgroup/system.slice/sshd.service", &[], &deny));
+        assert!(allowed("/sys/fs/cgroup/agent.slice/other.service", &[], &deny));
+        let allow = vec!["/sys/fs/cgroup/batch*".to_string()];
+        assert!(allowed("/sys/fs/cgroup/batch.slice/job-1", &allow, &deny));
+        assert!(!allowed("/sys/fs/cgroup/app.slice/db.service", &allow, &deny));
+    }
+}
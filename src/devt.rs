@@ -0,0 +1,58 @@
+// src/devt.rs
+//! dev_t encodings. The kernel's internal dev_t (inode->i_sb->s_dev, as seen from BPF)
+//! is 12-bit major / 20-bit minor; userspace st_dev uses the glibc 64-bit layout.
+
+/// (major, minor) from a kernel-internal dev_t.
+pub fn kernel_majmin(dev: u64) -> (u64, u64) {
+    ((dev >> 20) & 0xfff, dev & 0xfffff)
+}
+
+/// (major, minor) from a userspace st_dev / st_rdev.
+pub fn user_majmin(dev: u64) -> (u64, u64) {
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & 0xffff_ff00);
+    (major, minor)
+}
+
+/// (major, minor) from a "MAJ:MIN" field in hex, as in /proc/<pid>/maps.
+pub fn parse_hex_majmin(s: &str) -> Option<(u64, u64)> {
+    let (a, b) = s.split_once(':')?;
+    Some((u64::from_str_radix(a, 16).ok()?, u64::from_str_radix(b, 16).ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CASES: &[(u32, u32)] = &[
+        (0, 0), (8, 1), (259, 3), (253, 0x1_0000), (4095, 0xfffff),
+        (4096, 0), (4096, 5), (0x12345, 0xabcdef), (u32::MAX, u32::MAX),
+    ];
+
+    #[test]
+    fn user_round_trip() {
+        for &(ma, mi) in CASES {
+            let dev = libc::makedev(ma, mi);
+            assert_eq!(user_majmin(dev), (ma as u64, mi as u64), "{}:{}", ma, mi);
+            assert_eq!(user_majmin(dev), (libc::major(dev) as u64, libc::minor(dev) as u64));
+        }
+    }
+
+    #[test]
+    fn kernel_round_trip() {
+        // MKDEV(ma, mi) = ma << 20 | mi, the in-kernel encoding BPF reads from s_dev
+        for &(ma, mi) in CASES.iter().filter(|(ma, mi)| *ma < 1 << 12 && *mi < 1 << 20) {
+            let kdev = ((ma as u64) << 20) | mi as u64;
+            assert_eq!(kernel_majmin(kdev), (ma as u64, mi as u64));
+            // same device as userspace sees it
+            assert_eq!(kernel_majmin(kdev), user_majmin(libc::makedev(ma, mi)));
+        }
+    }
+
+    #[test]
+    fn maps_field() {
+        assert_eq!(parse_hex_majmin("fd:01"), Some((0xfd, 1)));
+        assert_eq!(parse_hex_majmin("103:02"), Some((0x103, 2)));
+        assert_eq!(parse_hex_majmin("zz"), None);
+    }
+}
@@ -4,6 +4,10 @@ pub enum CommEv {
      Wake { waker: u32, wakee: u32 }, 
      Futex { uaddr: u64, tid: u32, op: u32 } }
 
+/// Must match EVT_* in bpf/common.h
+pub const EVT_FUTEX_SPIKE: u32 = 1;
+pub const EVT_PROC_EXIT: u32 = 2;
+
 #[derive(Debug, Clone, Copy)]
 pub struct TunerEvent 
 { pub pid: u32, pub kind: u32, pub val_us: u64, pub ts_ns: u64 }
@@ -0,0 +1,151 @@
+// src/hist.rs
+use serde::Serialize;
+
+/// Must match HIST_SLOTS in bpf/common.h
+pub const HIST_SLOTS: usize = 32;
+
+/// Must match HIST_* kinds in bpf/tuner.bpf.c
+pub const HIST_RUNQ: u32 = 1;
+pub const HIST_FUTEX: u32 = 2;
+pub const HIST_FAULT: u32 = 3;
+pub const HIST_IO: u32 = 4;
+
+/// log2 histogram as laid out by BPF: slot i counts values in [2^i, 2^(i+1)) usec.
+#[derive(Clone, Copy, Debug)]
+pub struct Log2Hist { pub slots: [u64; HIST_SLOTS] }
+
+impl Default for Log2Hist {
+    fn default() -> Self { Self { slots: [0; HIST_SLOTS] } }
+}
+
+impl Log2Hist {
+    pub fn from_bytes(b: &[u8]) -> Self {
+        let mut out = Self::default();
+        for (i, chunk) in b.chunks_exact(8).take(HIST_SLOTS).enumerate() {
+            out.slots[i] = u64::from_ne_bytes(chunk.try_into().unwrap());
+        }
+        out
+    }
+
+    /// Count one value, bucketed the way hist_add does in BPF.
+    pub fn record(&mut self, val_us: u64) {
+        let slot = if val_us == 0 { 0 } else { 63 - val_us.leading_zeros() as usize };
+        self.slots[slot.min(HIST_SLOTS - 1)] += 1;
+    }
+
+    pub fn add(&mut self, other: &Log2Hist) {
+        for i in 0..HIST_SLOTS { self.slots[i] = self.slots[i].saturating_add(other.slots[i]); }
+    }
+
+    /// Interval delta against an earlier cumulative reading.
+    pub fn delta(&self, prev: &Log2Hist) -> Log2Hist {
+        let mut out = Log2Hist::default();
+        for i in 0..HIST_SLOTS { out.slots[i] = self.slots[i].saturating_sub(prev.slots[i]); }
+        out
+    }
+
+    pub fn count(&self) -> u64 { self.slots.iter().sum() }
+
+    /// Percentile in usec, linearly interpolated inside the matching bucket.
+    pub fn percentile(&self, q: f64) -> f64 {
+        let total = self.count();
+        if total == 0 { return 0.0; }
+        let rank = (q.clamp(0.0, 1.0) * total as f64).max(1.0);
+        let mut seen = 0u64;
+        for (i, &c) in self.slots.iter().enumerate() {
+            if c == 0 { continue; }
+            if (seen + c) as f64 >= rank {
+                let lo = if i == 0 { 0.0 } else { (1u64 << i) as f64 };
+                let hi = (1u64 << (i + 1)) as f64;
+                let frac = (rank - seen as f64) / c as f64;
+                return lo + (hi - lo) * frac;
+            }
+            seen += c;
+        }
+        (1u64 << HIST_SLOTS) as f64
+    }
+
+    pub fn summary(&self) -> HistSummary {
+        HistSummary {
+            count: self.count(),
+            p50_us: self.percentile(0.50),
+            p90_us: self.percentile(0.90),
+            p99_us: self.percentile(0.99),
+            slots: self.slots.to_vec(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct HistSummary {
+    pub count: u64,
+    pub p50_us: f64,
+    pub p90_us: f64,
+    pub p99_us: f64,
+    pub slots: Vec<u64>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct LatencySnapshot {
+    pub runq: HistSummary,
+    pub futex: HistSummary,
+    pub fault: HistSummary,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hist(vals: &[u64]) -> Log2Hist {
+        let mut h = Log2Hist::default();
+        for &v in vals { h.record(v); }
+        h
+    }
+
+    #[test]
+    fn empty_percentile_is_zero() {
+        assert_eq!(Log2Hist::default().percentile(0.99), 0.0);
+    }
+
+    #[test]
+    fn record_buckets_like_bpf() {
+        let h = hist(&[0, 1, 2, 3, 4, 1023, 1024]);
+        assert_eq!(h.slots[0], 2);
+        assert_eq!(h.slots[1], 2);
+        assert_eq!(h.slots[2], 1);
+        assert_eq!(h.slots[9], 1);
+        assert_eq!(h.slots[10], 1);
+        let mut top = Log2Hist::default();
+        top.record(u64::MAX);
+        assert_eq!(top.slots[HIST_SLOTS - 1], 1);
+    }
+
+    #[test]
+    fn percentile_interpolates_within_bucket() {
+        // 100 samples in [64, 128)
+        let h = hist(&[100; 100]);
+        assert_eq!(h.percentile(0.5), 96.0);
+        assert_eq!(h.percentile(1.0), 128.0);
+        assert!((h.percentile(0.01) - 64.64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn percentile_picks_the_tail_bucket() {
+        // 98 fast samples, 2 slow ones: p50 stays low, p99 lands in the slow bucket
+        let mut vals = vec![3u64; 98];
+        vals.extend([5000, 5000]);
+        let h = hist(&vals);
+        assert!(h.percentile(0.50) < 4.0);
+        let p99 = h.percentile(0.99);
+        assert!((4096.0..8192.0).contains(&p99), "p99 {}", p99);
+        assert!(h.percentile(0.0) >= 2.0);
+    }
+
+    #[test]
+    fn delta_saturates_on_reset() {
+        let prev = hist(&[10, 10, 10]);
+        let cur = hist(&[10]);
+        assert_eq!(cur.delta(&prev).count(), 0);
+        assert_eq!(prev.delta(&cur).count(), 2);
+    }
+}
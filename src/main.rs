@@ -5,6 +5,12 @@ mod topology; mod bandit;
 mod planner;
 mod numa;
 mod rate_limit;
+mod hist;
+mod cgroups;
+mod neighbors;
+mod devt;
+mod pagecache;
+mod sockops;
 use std::sync::Arc;
 
 use anyhow::Result;
@@ -13,7 +19,7 @@ use tokio::{self, sync::Mutex};
 use tokio_util::sync::CancellationToken;
 
 #[derive(Copy, Clone, Debug, ValueEnum)]
-enum StrategyKind { Learned }
+enum StrategyKind { Learned, Heuristic }
 
 #[derive(Parser, Debug)]
 struct Opts {
@@ -35,6 +41,42 @@ struct Opts {
     attach_sockops: bool,
     #[arg(long, default_value_t=false)]
     dry_run: bool,
+    /// Latency statistic the learned score optimizes: mean, p50, p90 or p99
+    #[arg(long, default_value = "mean")]
+    objective: String,
+    /// Comma-separated cgroups the agent may modify besides the target ('*' suffix = prefix match)
+    #[arg(long)]
+    cg_allow: Option<String>,
+    /// Comma-separated cgroups the agent must never modify
+    #[arg(long)]
+    cg_deny: Option<String>,
+    /// JSON array of sockops rules (ports, cc, keepalive, buffers, ...); reloaded on change
+    #[arg(long)]
+    sockops_config: Option<String>,
+    /// Move NIC interrupts/queues with the target's CPUs: any of irq,rps,xps or all
+    #[arg(long)]
+    net_steer: Option<String>,
+    /// Opt in to real-time promotion of target threads whose comm matches this pattern
+    #[arg(long)]
+    rt_threads: Option<String>,
+    /// Policy for --rt-threads: fifo, rr or deadline (parameters from observed bursts)
+    #[arg(long, default_value = "fifo")]
+    rt_policy: String,
+    /// Prefetch bandwidth cap per interval, in MiB
+    #[arg(long)]
+    prefetch_mb_per_tick: Option<u64>,
+}
+
+/// Demotes RT threads and restores every journaled knob when main returns, whether by
+/// signal, error or panic. Demotion goes first: a cgroup's RT budget can't be taken back
+/// while RT threads still run in it.
+struct RestoreOnExit;
+
+impl Drop for RestoreOnExit {
+    fn drop(&mut self) {
+        crate::actions::rt::demote_all();
+        crate::actions::journal::rollback();
+    }
 }
 
 #[tokio::main(flavor = "multi_thread")]
@@ -46,6 +88,20 @@ async fn main() -> Result<()> {
     if let Some(ref p) = opts.log_json { std::env::set_var("AGENT_LOG_JSON", p); }
     if opts.no_cpuset { std::env::set_var("AGENT_NO_CPUSET", "1"); }
     if opts.dry_run { std::env::set_var("AGENT_DRY_RUN", "1"); }
+    std::env::set_var("AGENT_OBJECTIVE", &opts.objective);
+    if let Some(ref a) = opts.cg_allow { std::env::set_var("AGENT_CG_ALLOW", a); }
+    if let Some(ref d) = opts.cg_deny { std::env::set_var("AGENT_CG_DENY", d); }
+    if let Some(ref p) = opts.sockops_config { std::env::set_var("AGENT_SOCKOPS_CONFIG", p); }
+    if let Some(ref s) = opts.net_steer { std::env::set_var("AGENT_NET_STEER", s); }
+    if let Some(ref t) = opts.rt_threads {
+        std::env::set_var("AGENT_RT_THREADS", t);
+        std::env::set_var("AGENT_RT_POLICY", &opts.rt_policy);
+    }
+    if let Some(mb) = opts.prefetch_mb_per_tick { std::env::set_var("AGENT_PREFETCH_MB_PER_TICK", mb.to_string()); }
+
+    let _restore = RestoreOnExit;
+    // systemd and kubernetes stop the agent with SIGTERM, not Ctrl-C
+    let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
 
     let (tx, rx) = tokio::sync::mpsc::channel::<Event>(4096);
     let bpf = crate::bpf::AgentBpf::load_and_attach(opts.pid, opts.with_descendants, opts.follow_new, opts.attach_sockops)?;
@@ -57,8 +113,9 @@ async fn main() -> Result<()> {
         .and_then(|v| v.parse::<u64>().ok()).unwrap_or(10);
     spawn_ringbuf_poller(bpf.clone(), tx, child_token, Duration::from_millis(poll_ms));
 
-    let strategy = match opts.strategy {
-        StrategyKind::Learned => crate::policy::learned::LearnedStrategy::new(),
+    let strategy: Box<dyn crate::policy::Strategy> = match opts.strategy {
+        StrategyKind::Learned => Box::new(crate::policy::learned::LearnedStrategy::new()),
+        StrategyKind::Heuristic => Box::new(crate::policy::heuristic::HeuristicStrategy::new()),
     };
 
     let mut orch = Orchestrator {
@@ -72,11 +129,16 @@ async fn main() -> Result<()> {
 
     tokio::select! {
         res = orch.run() => res,
-        _ = signal::ctrl_c() => {
+        _ = tokio::signal::ctrl_c() => {
             eprintln!("[main] Ctrl-C; shutting down...");
             cancel.cancel();
             Ok(())
         }
+        _ = sigterm.recv() => {
+            eprintln!("[main] SIGTERM; shutting down...");
+            cancel.cancel();
+            Ok(())
+        }
     }
 
 }
\ No newline at end of file
@@ -7,6 +7,7 @@ use std::time::Instant;
 
 use std::path::{Path};
 use serde::Serialize;
+use crate::hist::{HistSummary, Log2Hist, LatencySnapshot, HIST_FAULT, HIST_FUTEX, HIST_IO, HIST_RUNQ};
 
 
 #[derive(Clone, Debug, Default, Serialize)]
@@ -34,9 +35,87 @@ pub struct Config {
 pub struct IoSnapshot {
     pub dev: String,
     pub seq_ratio: f64,
+    pub reqs: u64,
+    pub bytes: u64,
+    pub avg_req_kb: f64,
+    pub avg_lat_us: f64,
+    pub lat: HistSummary,
 }
 
 
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct MemSnapshot {
+    pub current: u64,
+    pub high: Option<u64>,
+    pub low: u64,
+    pub anon: u64,
+    pub file: u64,
+    pub inactive_file: u64,
+    pub working_set: u64,
+}
+
+/// cpu.stat of the target cgroup over the last interval.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct CpuStat {
+    pub usage_usec: u64,
+    pub nr_periods: u64,
+    pub nr_throttled: u64,
+    pub throttled_usec: u64,
+}
+
+impl CpuStat {
+    /// Share of enforcement periods in which the cgroup was throttled.
+    pub fn throttled_ratio(&self) -> f64 {
+        if self.nr_periods == 0 { 0.0 } else { self.nr_throttled as f64 / self.nr_periods as f64 }
+    }
+}
+
+/// THP usage of the target plus system thp_* vmstat deltas over the last interval.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ThpSnapshot {
+    pub anon_kb: u64,
+    pub anon_huge_kb: u64,
+    pub huge_ratio: f64,
+    pub thp_fault_alloc: u64,
+    pub thp_fault_fallback: u64,
+    pub thp_collapse_alloc: u64,
+    pub thp_split_page: u64,
+}
+
+/// One target thread's on-CPU behaviour over the last interval.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TaskBurst {
+    pub tid: i32,
+    pub comm: String,
+    pub oncpu_us: u64,
+    pub bursts: u64,
+    pub avg_burst_us: f64,
+    /// longest burst since the agent started
+    pub max_burst_us: u64,
+}
+
+/// TCP behaviour of the target cgroup over the last interval (sockops telemetry).
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct NetSnapshot {
+    pub rtt_samples: u64,
+    pub srtt_avg_us: f64,
+    pub rtt: HistSummary,
+    pub retrans: u64,
+    pub opened: u64,
+    pub closed: u64,
+    /// established since the agent attached and not yet closed
+    pub active: u64,
+    pub connect_avg_us: f64,
+    pub connect: HistSummary,
+}
+
+impl NetSnapshot {
+    /// Retransmits per RTT sample, a cheap loss signal.
+    pub fn retrans_ratio(&self) -> f64 {
+        if self.rtt_samples == 0 { 0.0 } else { self.retrans as f64 / self.rtt_samples as f64 }
+    }
+}
+
 #[derive(Clone, Debug, Default, Serialize)]
 pub struct Snapshot {
     pub target_pid: i32,
@@ -53,11 +132,25 @@ pub struct Snapshot {
     pub config: Config,
     pub psi: Option<Psi>,
     pub psi_mem: Option<Psi>,
+    pub lat: LatencySnapshot,
+    pub caps: crate::bpf::Capabilities,
+    pub mem: Option<MemSnapshot>,
+    pub cpu_stat: Option<CpuStat>,
+    pub neighbors: Vec<crate::neighbors::Neighbor>,
+    pub node_pages: std::collections::BTreeMap<u32, u64>,
+    pub numa_migration: Option<crate::actions::migrate::MigrationProgress>,
+    pub thp: Option<ThpSnapshot>,
+    pub prefetch: crate::actions::prefetch::PrefetchStats,
+    pub prefetch_events: crate::bpf::PrefetchEventCounts,
+    pub page_cache: Vec<crate::pagecache::FileResidency>,
+    pub net: Option<NetSnapshot>,
+    pub tasks: Vec<TaskBurst>,
+    pub interval_ms: u64,
 }
 
 #[derive(Clone, Debug)]
 pub enum Event {
-    PrefetchFault { tgid: u32, dev: u64, ino: u64, pgoff: u64, ts_ns: u64 },
+    PrefetchFault { tgid: u32, pid: u32, dev: u64, ino: u64, pgoff: u64, ts_ns: u64 },
     FutexSpike { us: u64 },
 }
 
@@ -65,7 +158,174 @@ static mut PREV_SCHED: Option<HashMap<i32,(u64,u64)>> = None;
 static mut EWMA_RUNQ: Option<f64> = None;
 static mut EWMA_FUTEX: Option<f64> = None;
 static mut PREV_FAULTS: Option<HashMap<i32,u64>> = None;
+static mut PREV_MAJFLT: Option<(i32, u64)> = None;
+static mut PREV_PF_EVENTS: Option<crate::bpf::PrefetchEventCounts> = None;
+static mut PREV_NET: Option<crate::bpf::NetCounters> = None;
+static mut PREV_TASKS: Option<HashMap<i32, (u64, u64)>> = None; // tid -> (total_oncpu_us, nr_bursts)
 static mut LAST_SAMPLE: Option<Instant> = None;
+static mut PREV_HIST: Option<HashMap<(u32,u32),Log2Hist>> = None;
+static mut PREV_LLC: Option<HashMap<u32,u64>> = None;
+
+/// LLC misses over the last interval, summed over all target tgids.
+fn llc_delta(bpf: &crate::bpf::AgentBpf, tgids: &[u32]) -> u64 {
+    let mut sum = 0u64;
+    unsafe {
+        let prev = PREV_LLC.get_or_insert_with(HashMap::new);
+        for &tgid in tgids {
+            let cur = bpf.read_llc_for_pid(tgid);
+            if let Some(p) = prev.get(&tgid) { sum = sum.saturating_add(cur.saturating_sub(*p)); }
+            prev.insert(tgid, cur);
+        }
+        prev.retain(|t, _| tgids.contains(t));
+    }
+    sum
+}
+
+/// Interval histogram of one kind, summed over all target tgids.
+fn hist_delta(bpf: &crate::bpf::AgentBpf, tgids: &[u32], kind: u32) -> Log2Hist {
+    let mut sum = Log2Hist::default();
+    unsafe {
+        let prev = PREV_HIST.get_or_insert_with(HashMap::new);
+        for &tgid in tgids {
+            let cur = bpf.read_hist_for_tgid(tgid, kind);
+            // the first reading of a tgid is cumulative since it became a target; only seed it
+            if let Some(p) = prev.get(&(tgid, kind)) { sum.add(&cur.delta(p)); }
+            prev.insert((tgid, kind), cur);
+        }
+        prev.retain(|(t, _), _| tgids.contains(t));
+    }
+    sum
+}
+
+static mut PREV_IO: Option<HashMap<u32,crate::bpf::IoPat>> = None;
+
+/// Block I/O completed by the target's tgids during the last interval.
+fn collect_io(bpf: &crate::bpf::AgentBpf, tgids: &[u32], target_pid: i32) -> Option<IoSnapshot> {
+    let (mut seq, mut rnd, mut bytes, mut reqs, mut lat_sum) = (0u64, 0u64, 0u64, 0u64, 0u64);
+    let mut majmin: Option<(u64,u64)> = None;
+    unsafe {
+        let prev = PREV_IO.get_or_insert_with(HashMap::new);
+        for &tgid in tgids {
+            let Some(cur) = bpf.read_io_pattern_for_pid(tgid) else { continue };
+            let p = prev.get(&tgid).copied().unwrap_or_default();
+            seq += cur.seq.saturating_sub(p.seq);
+            rnd += cur.rnd.saturating_sub(p.rnd);
+            bytes += cur.bytes.saturating_sub(p.bytes);
+            reqs += cur.reqs.saturating_sub(p.reqs);
+            lat_sum += cur.lat_us_sum.saturating_sub(p.lat_us_sum);
+            if cur.dev_major > 0 && cur.reqs > p.reqs {
+                majmin = Some((cur.dev_major as u64, cur.dev_minor as u64));
+            }
+            prev.insert(tgid, cur);
+        }
+        prev.retain(|t, _| tgids.contains(t));
+    }
+    let lat = hist_delta(bpf, tgids, HIST_IO).summary();
+    let dev = majmin.and_then(|(maj, min)| block_dev_name(maj, min))
+        .or_else(|| detect_io_dev(target_pid))?;
+    let total = (seq + rnd) as f64;
+    Some(IoSnapshot {
+        dev,
+        seq_ratio: if total > 0.0 { seq as f64 / total } else { 0.0 },
+        reqs,
+        bytes,
+        avg_req_kb: if reqs > 0 { bytes as f64 / reqs as f64 / 1024.0 } else { 0.0 },
+        avg_lat_us: if reqs > 0 { lat_sum as f64 / reqs as f64 } else { 0.0 },
+        lat,
+    })
+}
+
+fn read_cg_u64(cg: &str, file: &str) -> Option<u64> {
+    fs::read_to_string(format!("{}/{}", cg, file)).ok()?.trim().parse::<u64>().ok()
+}
+
+fn collect_mem(cg: &str) -> Option<MemSnapshot> {
+    let st = crate::actions::memory::MemState::read(cg)?;
+    Some(MemSnapshot {
+        current: st.current,
+        high: read_cg_u64(cg, "memory.high"),
+        low: read_cg_u64(cg, "memory.low").unwrap_or(0),
+        anon: st.anon,
+        file: st.file,
+        inactive_file: st.inactive_file,
+        working_set: st.working_set(),
+    })
+}
+
+pub(crate) fn read_cpu_stat(cg: &str) -> Option<CpuStat> {
+    let text = fs::read_to_string(format!("{}/cpu.stat", cg)).ok()?;
+    let mut st = CpuStat::default();
+    for line in text.lines() {
+        let Some((k, v)) = line.split_once(' ') else { continue };
+        let v = v.trim().parse::<u64>().unwrap_or(0);
+        match k {
+            "usage_usec" => st.usage_usec = v,
+            "nr_periods" => st.nr_periods = v,
+            "nr_throttled" => st.nr_throttled = v,
+            "throttled_usec" => st.throttled_usec = v,
+            _ => {}
+        }
+    }
+    Some(st)
+}
+
+static mut PREV_CPU_STAT: Option<(String, CpuStat)> = None;
+
+fn collect_cpu_stat(cg: &str) -> Option<CpuStat> {
+    let cur = read_cpu_stat(cg)?;
+    let prev = unsafe { PREV_CPU_STAT.replace((cg.to_string(), cur)) };
+    let p = match prev {
+        Some((pcg, p)) if pcg == cg => p,
+        _ => return Some(CpuStat::default()),
+    };
+    Some(CpuStat {
+        usage_usec: cur.usage_usec.saturating_sub(p.usage_usec),
+        nr_periods: cur.nr_periods.saturating_sub(p.nr_periods),
+        nr_throttled: cur.nr_throttled.saturating_sub(p.nr_throttled),
+        throttled_usec: cur.throttled_usec.saturating_sub(p.throttled_usec),
+    })
+}
+
+static mut PREV_VMSTAT_THP: Option<[u64; 4]> = None;
+
+fn collect_thp(pid: i32) -> Option<ThpSnapshot> {
+    let rollup = fs::read_to_string(format!("/proc/{}/smaps_rollup", pid)).ok()?;
+    let kb = |key: &str| rollup.lines()
+        .find(|l| l.starts_with(key))
+        .and_then(|l| l.split_whitespace().nth(1))
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(0);
+    let anon_kb = kb("Anonymous:");
+    let anon_huge_kb = kb("AnonHugePages:");
+
+    let vmstat = fs::read_to_string("/proc/vmstat").unwrap_or_default();
+    let keys = ["thp_fault_alloc", "thp_fault_fallback", "thp_collapse_alloc", "thp_split_page"];
+    let mut cur = [0u64; 4];
+    for line in vmstat.lines() {
+        let Some((k, v)) = line.split_once(' ') else { continue };
+        if let Some(i) = keys.iter().position(|x| *x == k) { cur[i] = v.trim().parse().unwrap_or(0); }
+    }
+    let prev = unsafe { PREV_VMSTAT_THP.replace(cur) }.unwrap_or(cur);
+    let d = |i: usize| cur[i].saturating_sub(prev[i]);
+    Some(ThpSnapshot {
+        anon_kb,
+        anon_huge_kb,
+        huge_ratio: if anon_kb > 0 { anon_huge_kb as f64 / anon_kb as f64 } else { 0.0 },
+        thp_fault_alloc: d(0),
+        thp_fault_fallback: d(1),
+        thp_collapse_alloc: d(2),
+        thp_split_page: d(3),
+    })
+}
+
+fn collect_latency(bpf: &crate::bpf::AgentBpf) -> LatencySnapshot {
+    let tgids = bpf.target_tgids();
+    LatencySnapshot {
+        runq: hist_delta(bpf, &tgids, HIST_RUNQ).summary(),
+        futex: hist_delta(bpf, &tgids, HIST_FUTEX).summary(),
+        fault: hist_delta(bpf, &tgids, HIST_FAULT).summary(),
+    }
+}
 
 fn update_futex_ewma(futex_us_now: f64) -> f64 {
     unsafe {
@@ -187,6 +447,78 @@ fn read_tid_minflt(pid: i32, tid: i32) -> Option<u64> {
     } else { None }
 }
 
+/// Process-wide major faults (field 12 of /proc/<pid>/stat).
+fn read_majflt(pid: i32) -> Option<u64> {
+    let s = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // comm may contain spaces; fields resume after the closing paren
+    let rest = &s[s.rfind(')')? + 2..];
+    rest.split_whitespace().nth(9)?.parse::<u64>().ok()
+}
+
+fn majflt_delta(pid: i32) -> u64 {
+    let Some(cur) = read_majflt(pid) else { return 0 };
+    unsafe {
+        let d = match PREV_MAJFLT {
+            Some((p, prev)) if p == pid => cur.saturating_sub(prev),
+            _ => 0,
+        };
+        PREV_MAJFLT = Some((pid, cur));
+        d
+    }
+}
+
+fn prefetch_events_delta(bpf: &crate::bpf::AgentBpf) -> crate::bpf::PrefetchEventCounts {
+    let cur = bpf.read_prefetch_counts();
+    unsafe {
+        let d = PREV_PF_EVENTS.map(|p| cur.delta(&p)).unwrap_or_default();
+        PREV_PF_EVENTS = Some(cur);
+        d
+    }
+}
+
+fn collect_tasks(bpf: &crate::bpf::AgentBpf, tids: &[i32]) -> Vec<TaskBurst> {
+    let prev = unsafe { PREV_TASKS.take().unwrap_or_default() };
+    let mut cur = HashMap::new();
+    let mut out = Vec::new();
+    for &tid in tids {
+        let Some(st) = bpf.read_task_stats(tid as u32) else { continue };
+        cur.insert(tid, (st.total_oncpu_us, st.nr_bursts));
+        let Some(&(pu, pb)) = prev.get(&tid) else { continue };
+        let oncpu_us = st.total_oncpu_us.saturating_sub(pu);
+        let bursts = st.nr_bursts.saturating_sub(pb);
+        out.push(TaskBurst {
+            tid,
+            comm: crate::actions::priority::comm_of(tid),
+            oncpu_us,
+            bursts,
+            avg_burst_us: if bursts > 0 { oncpu_us as f64 / bursts as f64 } else { 0.0 },
+            max_burst_us: st.max_burst_us,
+        });
+    }
+    unsafe { PREV_TASKS = Some(cur); }
+    out
+}
+
+fn collect_net(bpf: &crate::bpf::AgentBpf) -> Option<NetSnapshot> {
+    let cur = bpf.read_net()?;
+    let prev = unsafe { PREV_NET.replace(cur) }.unwrap_or_default();
+    let d = |a: u64, b: u64| a.saturating_sub(b);
+    let rtt_samples = d(cur.rtt_samples, prev.rtt_samples);
+    let connects = d(cur.connects, prev.connects);
+    let opened_total = cur.opened_active + cur.opened_passive;
+    Some(NetSnapshot {
+        rtt_samples,
+        srtt_avg_us: if rtt_samples > 0 { d(cur.srtt_us_sum, prev.srtt_us_sum) as f64 / rtt_samples as f64 } else { 0.0 },
+        rtt: cur.rtt.delta(&prev.rtt).summary(),
+        retrans: d(cur.retrans, prev.retrans),
+        opened: d(opened_total, prev.opened_active + prev.opened_passive),
+        closed: d(cur.closed, prev.closed),
+        active: opened_total.saturating_sub(cur.closed),
+        connect_avg_us: if connects > 0 { d(cur.connect_us_sum, prev.connect_us_sum) as f64 / connects as f64 } else { 0.0 },
+        connect: cur.connect.delta(&prev.connect).summary(),
+    })
+}
+
 fn read_psi(path: &str) -> f64 {
     if let Ok(s) = fs::read_to_string(path) {
         for line in s.lines() {
@@ -206,6 +538,7 @@ pub fn collect_snapshot(bpf: &crate::bpf::AgentBpf) -> Result<Snapshot> {
     let target_pid = std::env::var("TUNER_PID").ok().and_then(|s| s.parse::<i32>().ok()).unwrap_or(0);
     let tids = if target_pid > 0 { list_tids(target_pid) } else { Vec::new() };
     let threads = tids.len();
+    let target_cg = crate::cgroups::cgv2_path_of_pid(target_pid);
 
     let now = Instant::now();
     let dt_ms = unsafe {
@@ -271,15 +604,10 @@ pub fn collect_snapshot(bpf: &crate::bpf::AgentBpf) -> Result<Snapshot> {
         futex_ewma_us_mean,
         page_faults_sum,
         llc_delta_per_thread: {
-            let llc = bpf.read_llc_for_pid(target_pid as u32) as f64;
+            let llc = llc_delta(bpf, &bpf.target_tgids()) as f64;
             if threads > 0 { llc / threads as f64 } else { 0.0 }
         },
-        io: detect_io_dev(target_pid).and_then(|dev| {
-            let (seq,rnd) = bpf.read_io_pattern_for_pid(target_pid as u32);
-            let total = (seq + rnd) as f64;
-            let ratio = if total > 0.0 { (seq as f64)/total } else { 0.0 };
-            Some(IoSnapshot { dev, seq_ratio: ratio })
-        }),
+        io: collect_io(bpf, &bpf.target_tgids(), target_pid),
         total_cpus: read_online_cpus(),
         comm_wake: bpf.read_comm_wake(),
         comm_futex: futex_us_now as u64,
@@ -291,23 +619,38 @@ pub fn collect_snapshot(bpf: &crate::bpf::AgentBpf) -> Result<Snapshot> {
             min_switch_interval_ms: 1200,
         },
         psi: psi,
-        psi_mem: psi_mem
+        psi_mem: psi_mem,
+        lat: collect_latency(bpf),
+        caps: bpf.caps.clone(),
+        mem: if target_pid > 0 { collect_mem(&target_cg) } else { None },
+        cpu_stat: if target_pid > 0 { collect_cpu_stat(&target_cg) } else { None },
+        neighbors: if target_pid > 0 { crate::neighbors::collect(&target_cg, dt_ms) } else { Vec::new() },
+        node_pages: if target_pid > 0 { crate::numa::node_pages_for_pid(target_pid) } else { Default::default() },
+        numa_migration: crate::actions::migrate::progress(),
+        thp: if target_pid > 0 { collect_thp(target_pid) } else { None },
+        prefetch_events: prefetch_events_delta(bpf),
+        net: collect_net(bpf),
+        tasks: collect_tasks(bpf, &tids),
+        interval_ms: dt_ms,
+        page_cache: if target_pid > 0 { crate::pagecache::scan_pid(target_pid) } else { Vec::new() },
+        prefetch: crate::actions::prefetch::take_interval(if target_pid > 0 { majflt_delta(target_pid) } else { 0 }),
     })
 }
 
 
-fn detect_io_dev(pid: i32) -> Option<String> {
-    use std::os::unix::fs::MetadataExt;
+/// Backing block device of the first regular file (or block device node) the target has open.
+pub(crate) fn detect_io_dev(pid: i32) -> Option<String> {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
     use std::fs;
     let fd_dir = format!("/proc/{}/fd", pid);
     let mut majmin: Option<(u64,u64)> = None;
     if let Ok(rd) = fs::read_dir(fd_dir) {
         for e in rd.flatten() {
             if let Ok(path) = fs::read_link(e.path()) {
-                let meta = fs::metadata(&path).ok()?;
-                let rdev = meta.rdev();
-                let major = (rdev >> 8) & 0xfff;
-                let minor = (rdev & 0xff) | ((rdev >> 12) & 0xfff00);
+                let Ok(meta) = fs::metadata(&path) else { continue };
+                let ft = meta.file_type();
+                let rdev = if ft.is_file() { meta.dev() } else if ft.is_block_device() { meta.rdev() } else { continue };
+                let (major, minor) = crate::devt::user_majmin(rdev);
                 if major > 0 {
                     majmin = Some((major, minor));
                     break;
@@ -316,11 +659,11 @@ fn detect_io_dev(pid: i32) -> Option<String> {
         }
     }
     let (maj, min) = majmin?;
+    block_dev_name(maj, min)
+}
+
+fn block_dev_name(maj: u64, min: u64) -> Option<String> {
     let bb = format!("/sys/dev/block/{}:{}", maj, min);
-    if let Ok(name) = fs::read_link(&bb) {
-        if let Some(dev) = name.file_name().and_then(|s| s.to_str()) {
-            return Some(dev.to_string());
-        }
-    }
-    None
+    let name = fs::read_link(&bb).ok()?;
+    name.file_name().and_then(|s| s.to_str()).map(|s| s.to_string())
 }
\ No newline at end of file
@@ -0,0 +1,117 @@
+// src/neighbors.rs
+use serde::Serialize;
+use std::{collections::HashMap, fs, path::{Path, PathBuf}, time::{Duration, Instant}};
+
+const CG_ROOT: &str = "/sys/fs/cgroup";
+const MAX_DEPTH: usize = 4;
+const TOP_N: usize = 8;
+// the cgroup tree changes rarely; re-walk it on this cadence rather than every tick
+const RESCAN_DEFAULT_SECS: u64 = 10;
+
+/// A cgroup competing with the target, with interval usage.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Neighbor {
+    pub path: String,
+    pub cpu_usage_usec: u64,
+    pub cpu_pct: f64,
+    pub mem_current: u64,
+    pub io_bytes: u64,
+    pub psi_cpu_some10: f64,
+    pub score: f64,
+    pub touchable: bool,
+}
+
+static mut PREV: Option<HashMap<String, (u64, u64)>> = None; // path -> (usage_usec, io bytes)
+static mut WALKED: Option<(String, Instant, Vec<PathBuf>)> = None; // (target, when, candidates)
+
+/// Cgroups with member processes, excluding the target's own subtree and its ancestors.
+fn walk(dir: &Path, depth: usize, target: &Path, out: &mut Vec<PathBuf>) {
+    if depth > MAX_DEPTH { return; }
+    let Ok(rd) = fs::read_dir(dir) else { return };
+    for e in rd.flatten() {
+        let p = e.path();
+        if !e.file_type().map(|t| t.is_dir()).unwrap_or(false) { continue; }
+        if p.starts_with(target) { continue; }
+        let has_procs = fs::read_to_string(p.join("cgroup.procs"))
+            .map(|s| !s.trim().is_empty()).unwrap_or(false);
+        if has_procs && !target.starts_with(&p) { out.push(p.clone()); }
+        walk(&p, depth + 1, target, out);
+    }
+}
+
+fn io_bytes(cg: &Path) -> u64 {
+    let mut sum = 0u64;
+    if let Ok(s) = fs::read_to_string(cg.join("io.stat")) {
+        for tok in s.split_whitespace() {
+            if let Some(v) = tok.strip_prefix("rbytes=").or_else(|| tok.strip_prefix("wbytes=")) {
+                sum = sum.saturating_add(v.parse::<u64>().unwrap_or(0));
+            }
+        }
+    }
+    sum
+}
+
+fn psi_some10(path: &Path) -> f64 {
+    let s = fs::read_to_string(path).unwrap_or_default();
+    s.lines()
+        .find(|l| l.starts_with("some "))
+        .and_then(|l| l.split_whitespace().find_map(|t| t.strip_prefix("avg10=")))
+        .and_then(|v| v.parse::<f64>().ok())
+        .unwrap_or(0.0)
+}
+
+/// Candidate cgroups, re-walked every AGENT_NEIGHBOR_RESCAN_SECS or when the target moves.
+fn candidates(target_cg: &str) -> Vec<PathBuf> {
+    let every = std::env::var("AGENT_NEIGHBOR_RESCAN_SECS").ok()
+        .and_then(|v| v.parse::<u64>().ok()).unwrap_or(RESCAN_DEFAULT_SECS);
+    unsafe {
+        if let Some((t, at, paths)) = WALKED.as_ref() {
+            if t == target_cg && at.elapsed() < Duration::from_secs(every) { return paths.clone(); }
+        }
+        let mut paths = Vec::new();
+        walk(Path::new(CG_ROOT), 1, Path::new(target_cg), &mut paths);
+        WALKED = Some((target_cg.to_string(), Instant::now(), paths.clone()));
+        paths
+    }
+}
+
+/// Enumerate and rank other cgroups by how much CPU and I/O they consumed since the last call.
+pub fn collect(target_cg: &str, dt_ms: u64) -> Vec<Neighbor> {
+    let paths = candidates(target_cg);
+
+    let cpus = num_cpus::get().max(1) as f64;
+    let interval_us = (dt_ms.max(1) * 1000) as f64;
+    let mut cur: HashMap<String, (u64, u64)> = HashMap::new();
+    let mut out = Vec::new();
+    let prev = unsafe { PREV.take().unwrap_or_default() };
+    for p in paths {
+        // removed since the last walk
+        if !p.exists() { continue; }
+        let key = p.display().to_string();
+        let usage = crate::metrics::read_cpu_stat(&key).map(|c| c.usage_usec).unwrap_or(0);
+        let io = io_bytes(&p);
+        cur.insert(key.clone(), (usage, io));
+        let Some(&(pu, pio)) = prev.get(&key) else { continue };
+        let du = usage.saturating_sub(pu);
+        let dio = io.saturating_sub(pio);
+        let cpu_pct = 100.0 * du as f64 / (interval_us * cpus);
+        let mem_current = fs::read_to_string(p.join("memory.current")).ok()
+            .and_then(|s| s.trim().parse::<u64>().ok()).unwrap_or(0);
+        // one CPU-percent weighs like 1 MiB/s of I/O
+        let io_mib_s = dio as f64 / (1u64 << 20) as f64 / (interval_us / 1e6);
+        out.push(Neighbor {
+            path: key.clone(),
+            cpu_usage_usec: du,
+            cpu_pct,
+            mem_current,
+            io_bytes: dio,
+            psi_cpu_some10: psi_some10(&p.join("cpu.pressure")),
+            score: cpu_pct + io_mib_s,
+            touchable: crate::cgroups::may_touch(&key),
+        });
+    }
+    unsafe { PREV = Some(cur); }
+    out.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+    out.truncate(TOP_N);
+    out
+}
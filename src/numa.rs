@@ -1,21 +1,6 @@
 use anyhow::{Context, Result};
 use std::{collections::BTreeMap, fs};
-
-fn parse_cpu_list(s: &str) -> Vec<usize> {
-    let mut out = Vec::new();
-    for part in s.trim().split(',') {
-        if let Some((a,b)) = part.split_once('-') {
-            if let (Ok(a), Ok(b)) = (a.trim().parse::<usize>(), b.trim().parse::<usize>()) {
-                for x in a.min(b)..=a.max(b) { out.push(x); }
-            }
-        } else if let Ok(v) = part.trim().parse::<usize>() {
-            out.push(v);
-        }
-    }
-    out.sort_unstable();
-    out.dedup();
-    out
-}
+use crate::topology::{parse_cpu_list, Placement, Topology};
 
 pub fn cpu_topology() -> Result<BTreeMap<u32, Vec<usize>>> {
     let mut topo = BTreeMap::new();
@@ -40,12 +25,16 @@ pub fn cpu_topology() -> Result<BTreeMap<u32, Vec<usize>>> {
     Ok(topo)
 }
 
-pub fn dominant_node_for_pid(pid: i32) -> Option<u32> {
+/// Per-VMA node page counts from numa_maps: (vma start, node -> pages).
+pub fn numa_maps_vmas(pid: i32) -> Vec<(u64, BTreeMap<u32, u64>)> {
     let p = format!("/proc/{}/numa_maps", pid);
-    let text = fs::read_to_string(&p).ok()?;
-    let mut counts: BTreeMap<u32, u64> = BTreeMap::new();
+    let text = match fs::read_to_string(&p) { Ok(t) => t, Err(_) => return Vec::new() };
+    let mut out = Vec::new();
     for line in text.lines() {
-        for tok in line.split_whitespace() {
+        let mut toks = line.split_whitespace();
+        let Some(start) = toks.next().and_then(|a| u64::from_str_radix(a, 16).ok()) else { continue };
+        let mut counts: BTreeMap<u32, u64> = BTreeMap::new();
+        for tok in toks {
             if let Some(rest) = tok.strip_prefix('N') {
                 if let Some((n, v)) = rest.split_once('=') {
                     if let (Ok(n), Ok(v)) = (n.parse::<u32>(), v.parse::<u64>()) {
@@ -54,34 +43,179 @@ pub fn dominant_node_for_pid(pid: i32) -> Option<u32> {
                 }
             }
         }
+        if !counts.is_empty() { out.push((start, counts)); }
     }
-    counts.into_iter().max_by_key(|(_,v)| *v).map(|(n,_)| n)
+    out
 }
 
-pub fn pick_compact(node: u32, k: usize, topo: &BTreeMap<u32, Vec<usize>>) -> Vec<usize> {
-    if let Some(cpus) = topo.get(&node) {
-        return cpus.iter().cloned().take(k.max(1)).collect();
+/// Total resident pages per node for a process.
+pub fn node_pages_for_pid(pid: i32) -> BTreeMap<u32, u64> {
+    let mut total: BTreeMap<u32, u64> = BTreeMap::new();
+    for (_, counts) in numa_maps_vmas(pid) {
+        for (n, v) in counts { *total.entry(n).or_default() += v; }
     }
-    Vec::new()
+    total
+}
+
+pub fn dominant_node_for_pid(pid: i32) -> Option<u32> {
+    node_pages_for_pid(pid).into_iter().max_by_key(|(_,v)| *v).map(|(n,_)| n)
 }
 
-pub fn pick_spread(k: usize, topo: &BTreeMap<u32, Vec<usize>>) -> Vec<usize> {
-    if k == 0 || topo.is_empty() { return Vec::new(); }
-    let mut order: Vec<(u32, &Vec<usize>)> = topo.iter().map(|(n, v)| (*n, v)).collect();
-    order.sort_by_key(|(n,_)| *n);
-    let mut idx = vec![0usize; order.len()];
+fn round_robin(lists: &[Vec<usize>], k: usize) -> Vec<usize> {
+    let mut idx = vec![0usize; lists.len()];
     let mut out = Vec::new();
-    'outer: loop {
-        for (i, (_, list)) in order.iter().enumerate() {
-            if idx[i] < list.len() {
-                out.push(list[idx[i]]);
+    while out.len() < k {
+        let mut progressed = false;
+        for (i, l) in lists.iter().enumerate() {
+            if idx[i] < l.len() {
+                out.push(l[idx[i]]);
                 idx[i] += 1;
-                if out.len() >= k { break 'outer; }
+                progressed = true;
+                if out.len() >= k { break; }
             }
         }
-        if out.len() >= k || idx.iter().zip(order.iter()).all(|(i,(_,l))| *i >= l.len()) {
-            break;
-        }
+        if !progressed { break; }
     }
     out
-}
\ No newline at end of file
+}
+
+/// k CPUs on `node`. Llc: fit inside one LLC when possible, one thread per core first.
+/// Smt: same, but pack SMT siblings together so the fewest cores are used.
+pub fn pick_compact(node: u32, k: usize, topo: &Topology, mode: Placement) -> Vec<usize> {
+    let k = k.max(1);
+    if mode == Placement::Node {
+        return topo.nodes().get(&node).map(|c| c.iter().copied().take(k).collect()).unwrap_or_default();
+    }
+    let mut doms: Vec<Vec<usize>> = topo.llc_domains().into_iter()
+        .filter(|((n, _), _)| *n == node)
+        .map(|(_, v)| v)
+        .collect();
+    if mode == Placement::Smt {
+        for d in doms.iter_mut() {
+            d.sort_by_key(|c| topo.cpus.get(c).map(|i| (i.core, i.cpu)).unwrap_or((u32::MAX, *c)));
+        }
+    }
+    // largest LLC first so a small request lands in a single cache domain
+    doms.sort_by_key(|d| std::cmp::Reverse(d.len()));
+    doms.into_iter().flatten().take(k).collect()
+}
+
+/// k CPUs spread across nodes (Node) or LLC domains (Llc/Smt); Smt uses every core's
+/// primary thread before any sibling.
+pub fn pick_spread(k: usize, topo: &Topology, mode: Placement) -> Vec<usize> {
+    if k == 0 || topo.cpus.is_empty() { return Vec::new(); }
+    match mode {
+        Placement::Node => {
+            let lists: Vec<Vec<usize>> = topo.nodes().into_values().collect();
+            round_robin(&lists, k)
+        }
+        Placement::Llc => {
+            let lists: Vec<Vec<usize>> = topo.llc_domains().into_values().collect();
+            round_robin(&lists, k)
+        }
+        Placement::Smt => {
+            let doms = topo.llc_domains();
+            let prim: Vec<Vec<usize>> = doms.values()
+                .map(|d| d.iter().copied().filter(|c| topo.cpus[c].is_primary()).collect()).collect();
+            let sec: Vec<Vec<usize>> = doms.values()
+                .map(|d| d.iter().copied().filter(|c| !topo.cpus[c].is_primary()).collect()).collect();
+            let mut out = round_robin(&prim, k);
+            if out.len() < k { out.extend(round_robin(&sec, k - out.len())); }
+            out
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn llcs_of(topo: &Topology, cpus: &[usize]) -> BTreeSet<u32> {
+        cpus.iter().map(|c| topo.cpus[c].llc).collect()
+    }
+
+    fn nodes_of(topo: &Topology, cpus: &[usize]) -> BTreeSet<u32> {
+        cpus.iter().map(|c| topo.cpus[c].node).collect()
+    }
+
+    #[test]
+    fn compact_stays_in_one_llc() {
+        // 2 sockets x 2 LLCs x 4 cores x SMT2 = 32 cpus
+        let t = Topology::synthetic(2, 2, 4, 2, &[]);
+        for mode in [Placement::Llc, Placement::Smt] {
+            for k in 1..=8 {
+                let cpus = pick_compact(1, k, &t, mode);
+                assert_eq!(cpus.len(), k);
+                assert_eq!(nodes_of(&t, &cpus), BTreeSet::from([1]), "{:?} k={}", mode, k);
+                assert_eq!(llcs_of(&t, &cpus).len(), 1, "{:?} k={}", mode, k);
+            }
+        }
+        let node = pick_compact(0, 16, &t, Placement::Node);
+        assert_eq!(nodes_of(&t, &node), BTreeSet::from([0]));
+    }
+
+    #[test]
+    fn compact_llc_uses_siblings_last() {
+        let t = Topology::synthetic(2, 2, 4, 2, &[]);
+        let cpus = pick_compact(0, 4, &t, Placement::Llc);
+        assert!(cpus.iter().all(|c| t.cpus[c].is_primary()), "{:?}", cpus);
+        let cpus = pick_compact(0, 6, &t, Placement::Llc);
+        assert_eq!(cpus.iter().filter(|c| !t.cpus[c].is_primary()).count(), 2);
+    }
+
+    #[test]
+    fn compact_smt_packs_cores() {
+        let t = Topology::synthetic(2, 2, 4, 2, &[]);
+        let cpus = pick_compact(0, 4, &t, Placement::Smt);
+        let cores: BTreeSet<u32> = cpus.iter().map(|c| t.cpus[c].core).collect();
+        assert_eq!(cores.len(), 2, "{:?}", cpus);
+    }
+
+    #[test]
+    fn spread_touches_every_llc() {
+        let t = Topology::synthetic(2, 2, 4, 2, &[]);
+        for mode in [Placement::Llc, Placement::Smt] {
+            let cpus = pick_spread(4, &t, mode);
+            assert_eq!(llcs_of(&t, &cpus).len(), 4, "{:?}", mode);
+            assert_eq!(nodes_of(&t, &cpus).len(), 2);
+        }
+        let cpus = pick_spread(2, &t, Placement::Node);
+        assert_eq!(nodes_of(&t, &cpus).len(), 2);
+    }
+
+    #[test]
+    fn spread_smt_uses_siblings_last() {
+        let t = Topology::synthetic(2, 2, 4, 2, &[]);
+        let cpus = pick_spread(16, &t, Placement::Smt);
+        assert!(cpus.iter().all(|c| t.cpus[c].is_primary()));
+        let cpus = pick_spread(17, &t, Placement::Smt);
+        assert_eq!(cpus.iter().filter(|c| !t.cpus[c].is_primary()).count(), 1);
+        let all = pick_spread(64, &t, Placement::Smt);
+        assert_eq!(all.len(), 32);
+    }
+
+    #[test]
+    fn single_node() {
+        let t = Topology::synthetic(1, 1, 4, 1, &[]);
+        assert_eq!(pick_compact(0, 2, &t, Placement::Llc), vec![0, 1]);
+        assert_eq!(pick_spread(3, &t, Placement::Llc), vec![0, 1, 2]);
+        assert_eq!(pick_spread(3, &t, Placement::Node), vec![0, 1, 2]);
+        assert!(pick_compact(1, 2, &t, Placement::Llc).is_empty());
+    }
+
+    #[test]
+    fn offline_gap_is_never_picked() {
+        // cpus 2 and 9 offline, both on node 0: a primary thread and another core's sibling
+        let t = Topology::synthetic(2, 1, 4, 2, &[2, 9]);
+        for mode in [Placement::Node, Placement::Llc, Placement::Smt] {
+            let spread = pick_spread(t.cpus.len(), &t, mode);
+            assert_eq!(spread.len(), 14, "{:?}", mode);
+            let compact = pick_compact(0, 8, &t, mode);
+            assert_eq!(compact.len(), 6, "{:?}", mode);
+            for c in spread.iter().chain(&compact) {
+                assert!(t.cpus.contains_key(c), "{:?} picked offline cpu {}", mode, c);
+            }
+        }
+    }
+}
@@ -11,28 +11,33 @@ use crate::metrics::{Event, Snapshot};
 use std::{fs};
 use crate::planner::lower_numa_plans;
 use crate::rate_limit::{log_tick, ActionGate, TickScheduler};
-pub struct Orchestrator<S: Strategy> { bpf: crate::bpf::AgentBpf, strategy: S, interval: std::time::Duration, log: Option<std::fs::File> }
+use crate::cgroups::cgv2_path_of_pid;
+use crate::planner::revalidate_cpusets;
+use crate::topology::TopologyWatcher;
+use std::collections::HashMap;
+pub struct Orchestrator<S: Strategy> {
+    bpf: crate::bpf::AgentBpf,
+    strategy: S,
+    interval: std::time::Duration,
+    log: Option<std::fs::File>,
+    topo: TopologyWatcher,
+    // cgroup -> cpus we last wrote, re-validated on topology changes
+    applied_cpusets: HashMap<String, Vec<usize>>,
+}
 use tokio::time::{interval, MissedTickBehavior};
 
 
-fn cgv2_path_of_pid(pid: i32) -> String {
-    let cgfile = format!("/proc/{}/cgroup", pid);
-    if let Ok(s) = fs::read_to_string(&cgfile) {
-        for line in s.lines() {
-            if let Some(rest) = line.splitn(3, ':').nth(2) {
-                return format!("/sys/fs/cgroup{}", rest.trim());
-            }
-        }
-    }
-    "/sys/fs/cgroup".to_string()
-}
 static mut LAST_DUMP: Option<std::time::Instant> = None;
 
 impl<S: Strategy> Orchestrator<S> {
     pub fn new(bpf: crate::bpf::AgentBpf, strategy: S, interval_ms: u64) -> Self {
         let log = std::env::var("AGENT_LOG_JSON").ok().and_then(|p| OpenOptions::new().create(true).append(true).open(p).ok());
 
-        Self { bpf, strategy, interval: std::time::Duration::from_millis(interval_ms), log }
+        Self {
+            bpf, strategy, interval: std::time::Duration::from_millis(interval_ms), log,
+            topo: TopologyWatcher::new(),
+            applied_cpusets: HashMap::new(),
+        }
     }
     pub async fn run(&mut self) -> Result<()> {
         let idle_per_thread = (self.interval.as_millis() as f64) * 1000.0 * 0.05;
@@ -52,13 +57,26 @@ impl<S: Strategy> Orchestrator<S> {
             }).await??;
         let mut actions: Vec<Action> = self.strategy.tick(&snap);
 
+        crate::actions::rt::watchdog(&snap);
+        let rt = crate::actions::rt::RtLimits::from_env();
+        if rt.enabled {
+            let threads = crate::actions::rt::plan(&snap, &rt);
+            if !threads.is_empty() { actions.push(Action::PromoteRt { threads }); }
+        }
+
 
         self.bpf.poll();
+        self.bpf.refresh_sock_rules();
+
+        for tgid in self.bpf.drain_exited() {
+            crate::actions::prefetch::evict_tgid(tgid);
+        }
 
     
         for pevt in self.bpf.drain_prefetch_events() {
             let evt = Event::PrefetchFault {
                 tgid:  pevt.tgid,
+                pid:   pevt.pid,
                 dev:   pevt.sb_dev, 
                 ino:   pevt.ino,
                 pgoff: pevt.pgoff,
@@ -73,9 +91,12 @@ impl<S: Strategy> Orchestrator<S> {
         let dry = std::env::var("AGENT_DRY_RUN").map(|v| v=="1" || v.eq_ignore_ascii_case("true")).unwrap_or(false);
 
         let cg = cgv2_path_of_pid(snap.target_pid as i32);
-        let applier = Applier { cg, dry };
+        let applier = Applier { cg, dry, pid: snap.target_pid, threads: None };
         
-        actions = lower_numa_plans(actions, &snap, snap.target_pid as i32);
+        if self.topo.refresh(&applier.cg) {
+            actions.extend(revalidate_cpusets(&self.applied_cpusets, self.topo.topology()));
+        }
+        actions = lower_numa_plans(actions, &snap, snap.target_pid as i32, self.topo.topology());
 
         actions = gate.filter(&snap, actions);
 
@@ -83,7 +104,13 @@ impl<S: Strategy> Orchestrator<S> {
             eprintln!("[dry-run] actions: {:?}", actions);
         } else {
             eprintln!("actions: {:?}", actions);
-            applier.apply_all(&actions)?;
+            applier.apply_all(&actions);
+            for a in &actions {
+                if let Action::SetCpuset { cgroup, cpus } = a {
+                    let key = if cgroup.is_empty() { applier.cg.clone() } else { cgroup.clone() };
+                    self.applied_cpusets.insert(key, cpus.clone());
+                }
+            }
         }
         if let Some(mut file) = self.log.as_ref() {
             let kinds: Vec<String> = actions.iter().map(|a| crate::rate_limit::stable_key(a)).collect();
@@ -0,0 +1,178 @@
+// src/pagecache.rs
+//! Page-cache residency of files, via cachestat(2) (6.5+) or mincore(2) on a
+//! temporary mapping.
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::{collections::HashSet, fs, fs::File, os::fd::AsRawFd, os::unix::fs::MetadataExt, sync::Mutex, time::{Duration, Instant}};
+
+const SYS_CACHESTAT: libc::c_long = 451;
+const MAX_FILES: usize = 32;
+// mincore fallback maps at most this much of a file
+const MINCORE_MAX_BYTES: u64 = 1 << 30;
+const SCAN_DEFAULT_SECS: u64 = 5;
+
+#[repr(C)]
+struct CachestatRange { off: u64, len: u64 }
+
+#[repr(C)]
+#[derive(Default)]
+struct Cachestat {
+    nr_cache: u64,
+    nr_dirty: u64,
+    nr_writeback: u64,
+    nr_evicted: u64,
+    nr_recently_evicted: u64,
+}
+
+lazy_static! {
+    static ref CACHESTAT_OK: bool = probe_cachestat();
+    // (pid, when, result) of the last scan_pid
+    static ref LAST_SCAN: Mutex<Option<(i32, Instant, Vec<FileResidency>)>> = Mutex::new(None);
+}
+
+fn page_size() -> u64 {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as u64 }
+}
+
+fn cachestat(f: &File, off: u64, len: u64) -> std::io::Result<Cachestat> {
+    let range = CachestatRange { off, len };
+    let mut cs = Cachestat::default();
+    let rc = unsafe {
+        libc::syscall(SYS_CACHESTAT, f.as_raw_fd(), &range as *const CachestatRange, &mut cs as *mut Cachestat, 0u32)
+    };
+    if rc < 0 { return Err(std::io::Error::last_os_error()); }
+    Ok(cs)
+}
+
+fn probe_cachestat() -> bool {
+    match File::open("/proc/self/exe").and_then(|f| cachestat(&f, 0, 0)) {
+        Ok(_) => true,
+        Err(e) => { eprintln!("[agent] cachestat unavailable, using mincore: {e}"); false }
+    }
+}
+
+pub fn cachestat_supported() -> bool { *CACHESTAT_OK }
+
+/// Per-page residency of `[off, off+len)` through a private read-only mapping.
+pub fn mincore_pages(f: &File, off: u64, len: u64) -> Result<Vec<bool>> {
+    let ps = page_size();
+    let start = off / ps * ps;
+    let end = (off + len).min(f.metadata()?.len());
+    if end <= start { return Ok(Vec::new()); }
+    let maplen = (end - start) as usize;
+    let npages = maplen.div_ceil(ps as usize);
+    let mut vec = vec![0u8; npages];
+    unsafe {
+        let addr = libc::mmap(std::ptr::null_mut(), maplen, libc::PROT_READ, libc::MAP_SHARED, f.as_raw_fd(), start as libc::off_t);
+        if addr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error()).context("mmap for mincore");
+        }
+        let rc = libc::mincore(addr, maplen, vec.as_mut_ptr());
+        let err = std::io::Error::last_os_error();
+        libc::munmap(addr, maplen);
+        if rc != 0 { return Err(err).context("mincore"); }
+    }
+    Ok(vec.into_iter().map(|b| b & 1 == 1).collect())
+}
+
+/// Cached bytes in `[off, off+len)`; `len == 0` means to end of file.
+pub fn cached_bytes(f: &File, off: u64, len: u64) -> Result<u64> {
+    let ps = page_size();
+    if cachestat_supported() {
+        return Ok(cachestat(f, off, len).context("cachestat")?.nr_cache * ps);
+    }
+    let len = if len == 0 { f.metadata()?.len().saturating_sub(off) } else { len };
+    let pages = mincore_pages(f, off, len.min(MINCORE_MAX_BYTES))?;
+    Ok(pages.iter().filter(|&&r| r).count() as u64 * ps)
+}
+
+/// Residency of one file the target has open or mapped.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct FileResidency {
+    pub path: String,
+    pub dev: u64,
+    pub ino: u64,
+    pub size_bytes: u64,
+    pub cached_bytes: u64,
+    pub ratio: f64,
+    // cachestat only
+    pub dirty_bytes: Option<u64>,
+    pub recently_evicted_bytes: Option<u64>,
+}
+
+/// Regular files a process has open (/proc/<pid>/fd) or file-mapped (/proc/<pid>/maps).
+pub fn files_of_pid(pid: i32) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    let mut seen = HashSet::new();
+    if let Ok(rd) = fs::read_dir(format!("/proc/{}/fd", pid)) {
+        for e in rd.flatten() {
+            let Ok(t) = fs::read_link(e.path()) else { continue };
+            let p = t.display().to_string();
+            if p.starts_with('/') && seen.insert(p.clone()) { out.push(p); }
+        }
+    }
+    if let Ok(maps) = fs::read_to_string(format!("/proc/{}/maps", pid)) {
+        for line in maps.lines() {
+            let mut parts = line.split_whitespace();
+            let ino = parts.nth(4).unwrap_or("0");
+            let path = parts.collect::<Vec<_>>().join(" ");
+            if ino != "0" && path.starts_with('/') && !path.ends_with("(deleted)") && seen.insert(path.clone()) {
+                out.push(path);
+            }
+        }
+    }
+    out
+}
+
+pub fn residency_of(path: &str) -> Option<FileResidency> {
+    let f = File::open(path).ok()?;
+    let md = f.metadata().ok()?;
+    if !md.is_file() || md.len() == 0 { return None; }
+    let ps = page_size();
+    let (cached, dirty, evicted) = if cachestat_supported() {
+        let cs = cachestat(&f, 0, 0).ok()?;
+        (cs.nr_cache * ps, Some(cs.nr_dirty * ps), Some(cs.nr_recently_evicted * ps))
+    } else {
+        (cached_bytes(&f, 0, 0).ok()?, None, None)
+    };
+    Some(FileResidency {
+        path: path.to_string(),
+        dev: md.dev(),
+        ino: md.ino(),
+        size_bytes: md.len(),
+        cached_bytes: cached,
+        ratio: (cached as f64 / md.len() as f64).min(1.0),
+        dirty_bytes: dirty,
+        recently_evicted_bytes: evicted,
+    })
+}
+
+/// Residency of the target's MAX_FILES largest files, largest first. Measuring is costly
+/// (mincore maps up to 1 GiB per file), so it runs every AGENT_PAGECACHE_SCAN_SECS and
+/// the last result is returned in between.
+pub fn scan_pid(pid: i32) -> Vec<FileResidency> {
+    let every = std::env::var("AGENT_PAGECACHE_SCAN_SECS").ok()
+        .and_then(|v| v.parse::<u64>().ok()).unwrap_or(SCAN_DEFAULT_SECS);
+    let mut last = LAST_SCAN.lock().unwrap();
+    if let Some((p, at, files)) = last.as_ref() {
+        if *p == pid && at.elapsed() < Duration::from_secs(every) { return files.clone(); }
+    }
+    let out: Vec<FileResidency> = largest(files_of_pid(pid)).iter().filter_map(|p| residency_of(p)).collect();
+    *last = Some((pid, Instant::now(), out.clone()));
+    out
+}
+
+/// The MAX_FILES largest regular files of `paths`, largest first, picked by a cheap stat
+/// so that only those need measuring.
+pub fn largest(paths: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut cands: Vec<(String, u64)> = paths.into_iter()
+        .filter_map(|p| {
+            let md = fs::metadata(&p).ok()?;
+            (md.is_file() && md.len() > 0).then_some((p, md.len()))
+        })
+        .collect();
+    cands.sort_by_key(|c| std::cmp::Reverse(c.1));
+    cands.truncate(MAX_FILES);
+    cands.into_iter().map(|(p, _)| p).collect()
+}
@@ -1,34 +1,141 @@
-use crate::{actions::Action, metrics::Snapshot, numa};
+use crate::{actions::{migrate, Action}, metrics::Snapshot, numa};
+use crate::topology::{Placement, Topology};
+use crate::cgroups::cgv2_path_of_pid;
+use crate::actions::affinity::cpuset_writable;
+use crate::actions::net::Steer;
+use std::collections::HashMap;
 
-pub fn lower_numa_plans(actions: Vec<Action>, snap: &Snapshot, pid: i32) -> Vec<Action> {
+fn no_cpuset() -> bool {
+    std::env::var("AGENT_NO_CPUSET").map(|v| v == "1" || v.eq_ignore_ascii_case("true")).unwrap_or(false)
+}
+
+/// Cgroup cpuset when allowed and writable, per-task affinity otherwise.
+fn placement_action(cg: &str, cpus: Vec<usize>) -> Action {
+    if no_cpuset() || !cpuset_writable(cg) {
+        Action::SetAffinity { cgroup: String::new(), cpus }
+    } else {
+        Action::SetCpuset { cgroup: String::new(), cpus }
+    }
+}
+
+/// AGENT_NET_STEER=irq,rps,xps (any subset, or "all"); unset = leave the NIC alone.
+fn net_steer() -> Option<Steer> {
+    let v = std::env::var("AGENT_NET_STEER").unwrap_or_default().to_ascii_lowercase();
+    let has = |k: &str| v == "all" || v.split(',').any(|p| p.trim() == k);
+    let s = Steer { irq: has("irq"), rps: has("rps"), xps: has("xps") };
+    (s.irq || s.rps || s.xps).then_some(s)
+}
+
+/// Placement for the target, followed by moving its NIC queues along when enabled.
+fn place(out: &mut Vec<Action>, cg: &str, cpus: Vec<usize>) {
+    if let Some(steer) = net_steer() {
+        out.push(Action::SteerNet { iface: String::new(), cpus: cpus.clone(), steer });
+    }
+    out.push(placement_action(cg, cpus));
+}
+
+/// CPUs for packing `threads` onto `node`: one per thread, capped at the node's size,
+/// filling a single LLC first when it is big enough.
+pub fn plan_compact(node: u32, threads: usize, topo: &Topology, mode: Placement) -> Vec<usize> {
+    let per_node = topo.nodes().get(&node).map(|v| v.len()).unwrap_or(0);
+    if per_node == 0 { return Vec::new(); }
+    numa::pick_compact(node, threads.clamp(1, per_node), topo, mode)
+}
+
+/// `topo` is the watcher's view, already restricted to online CPUs in the parent's effective cpuset.
+pub fn lower_numa_plans(actions: Vec<Action>, snap: &Snapshot, pid: i32, topo: &Topology) -> Vec<Action> {
     use Action::*;
-    let topo = match numa::cpu_topology() {
-        Ok(t) => t,
-        Err(_) => return actions,
-    };
+    if topo.cpus.is_empty() { return actions; }
+    let mode = Placement::from_env();
+    let cg = cgv2_path_of_pid(pid);
     let mut out = Vec::new();
     for a in actions {
         match a {
             CompactWithinNUMA { node } => {
-                let sel = node.or_else(|| numa::dominant_node_for_pid(pid));
-                if let Some(n) = sel {
-                    let need = (snap.threads as usize).max(1).min(snap.total_cpus.max(1));
-                let cpus = {
-                    let per_node = topo.get(&n).map(|v| v.len()).unwrap_or(0);
-                    let need = (snap.threads as usize).min(per_node.max(1));
-                    numa::pick_compact(n, need, &topo)
-                };
+                let dominant = numa::dominant_node_for_pid(pid);
+                let Some(n) = node.or(dominant).or_else(|| topo.nodes().keys().next().copied()) else { continue };
+                // threads go to n; pull their memory along if it lives elsewhere
+                if dominant.is_some() && dominant != Some(n) {
+                    out.push(MigrateMemory { to_node: n, max_bytes: migrate::budget_bytes() });
+                }
+                let cpus = plan_compact(n, snap.threads, topo, mode);
+                if !cpus.is_empty() {
+                    place(&mut out, &cg, cpus);
                 }
             }
             SpreadAcrossNUMA { width } => {
-                let k = width.max(1).min(snap.total_cpus.max(1));
-                let cpus = numa::pick_spread(k, &topo);
+                let k = width.max(1).min(topo.cpus.len());
+                let cpus = numa::pick_spread(k, topo, mode);
                 if !cpus.is_empty() {
-                    out.push(SetCpuset { cgroup: String::new(), cpus });
+                    place(&mut out, &cg, cpus);
                 }
             }
+            SetCpuset { cgroup, cpus } => {
+                let cpus: Vec<usize> = cpus.into_iter().filter(|c| topo.cpus.contains_key(c)).collect();
+                if !cpus.is_empty() { out.push(SetCpuset { cgroup, cpus }); }
+            }
             other => out.push(other),
         }
     }
     out
-}
\ No newline at end of file
+}
+
+/// After a topology change, re-issue applied cpusets that now name unusable CPUs:
+/// keep the surviving CPUs, topping up with a fresh spread of the same width.
+pub fn revalidate_cpusets(applied: &HashMap<String, Vec<usize>>, topo: &Topology) -> Vec<Action> {
+    let mode = Placement::from_env();
+    let mut out = Vec::new();
+    for (cg, cpus) in applied {
+        let keep: Vec<usize> = cpus.iter().copied().filter(|c| topo.cpus.contains_key(c)).collect();
+        if keep.len() == cpus.len() { continue; }
+        let mut next = keep;
+        for c in numa::pick_spread(topo.cpus.len(), topo, mode) {
+            if next.len() >= cpus.len() { break; }
+            if !next.contains(&c) { next.push(c); }
+        }
+        if !next.is_empty() {
+            out.push(Action::SetCpuset { cgroup: cg.clone(), cpus: next });
+        }
+    }
+    out
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plan_compact_caps_at_node_size() {
+        let t = Topology::synthetic(2, 2, 4, 2, &[]);
+        let cpus = plan_compact(1, 64, &t, Placement::Llc);
+        assert_eq!(cpus.len(), 16);
+        assert!(cpus.iter().all(|c| t.cpus[c].node == 1));
+        assert_eq!(plan_compact(0, 0, &t, Placement::Llc).len(), 1);
+        assert!(plan_compact(7, 4, &t, Placement::Llc).is_empty());
+    }
+
+    #[test]
+    fn plan_compact_skips_offline_cpus() {
+        let t = Topology::synthetic(1, 2, 2, 1, &[1]);
+        let cpus = plan_compact(0, 8, &t, Placement::Llc);
+        assert_eq!(cpus.len(), 3);
+        assert!(!cpus.contains(&1));
+    }
+
+    #[test]
+    fn placement_falls_back_to_affinity() {
+        match placement_action("/nonexistent/cgroup", vec![0, 1]) {
+            Action::SetAffinity { cgroup, cpus } => { assert!(cgroup.is_empty()); assert_eq!(cpus, vec![0, 1]); }
+            a => panic!("expected SetAffinity, got {:?}", a),
+        }
+    }
+
+    #[test]
+    fn placement_uses_writable_cpuset() {
+        let dir = std::env::temp_dir().join(format!("planner-cg-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("cpuset.cpus"), "").unwrap();
+        let a = placement_action(dir.to_str().unwrap(), vec![3]);
+        std::fs::remove_dir_all(&dir).ok();
+        assert!(matches!(a, Action::SetCpuset { ref cpus, .. } if cpus == &vec![3]), "{:?}", a);
+    }
+}
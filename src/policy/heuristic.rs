@@ -0,0 +1,173 @@
+// src/policy/heuristic.rs
+use crate::{actions::Action, metrics::Snapshot};
+use super::Strategy;
+use std::{collections::HashMap, time::{Duration, Instant}};
+
+/// Thresholded rules; each rule fires at most once per regime change.
+#[derive(Clone)]
+pub struct HeuristicCfg {
+    pub psi_cpu_spread: f64,
+    pub runq_spread_us: f64,
+    pub io_seq_hi: f64,
+    pub io_seq_lo: f64,
+    pub io_min_reqs: u64,
+    pub psi_mem_protect: f64,
+    pub neighbor_cpu_pct: f64,
+    pub neighbor_weight: u32,
+    pub majflt_evict: u64,
+    pub evict_bytes: u64,
+    pub thp_min_anon_kb: u64,
+    pub thp_ratio_lo: f64,
+    pub thp_max_vmas: usize,
+}
+
+impl Default for HeuristicCfg {
+    fn default() -> Self {
+        Self {
+            psi_cpu_spread: 10.0,
+            runq_spread_us: 2000.0,
+            io_seq_hi: 0.8,
+            io_seq_lo: 0.2,
+            io_min_reqs: 32,
+            psi_mem_protect: 5.0,
+            neighbor_cpu_pct: 20.0,
+            neighbor_weight: 50,
+            majflt_evict: 200,
+            evict_bytes: 256 << 20,
+            thp_min_anon_kb: 512 << 10,
+            thp_ratio_lo: 0.2,
+            thp_max_vmas: 8,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum IoRegime { Sequential, Random }
+
+pub struct Heuristic {
+    cfg: HeuristicCfg,
+    io_regime: Option<IoRegime>,
+    spread: bool,
+    protected: bool,
+    demoted: Vec<String>,
+    evicted: HashMap<String, Instant>,
+    collapsed: bool,
+}
+
+pub type HeuristicStrategy = Heuristic;
+
+impl Heuristic {
+    pub fn new() -> Self { Self::with_cfg(HeuristicCfg::default()) }
+
+    pub fn with_cfg(cfg: HeuristicCfg) -> Self {
+        Self { cfg, io_regime: None, spread: false, protected: false, demoted: Vec::new(), evicted: HashMap::new(), collapsed: false }
+    }
+
+    fn io_rule(&mut self, snap: &Snapshot) -> Option<Action> {
+        let io = snap.io.as_ref()?;
+        if io.reqs < self.cfg.io_min_reqs { return None; }
+        let regime = if io.seq_ratio >= self.cfg.io_seq_hi {
+            IoRegime::Sequential
+        } else if io.seq_ratio <= self.cfg.io_seq_lo {
+            IoRegime::Random
+        } else {
+            return None;
+        };
+        if self.io_regime == Some(regime) { return None; }
+        self.io_regime = Some(regime);
+        let (ra, sched, nomerges) = match regime {
+            IoRegime::Sequential => (2048, "mq-deadline", 0),
+            IoRegime::Random => (64, "none", 1),
+        };
+        Some(Action::TuneBlockDev {
+            dev: io.dev.clone(),
+            readahead_kb: Some(ra),
+            scheduler: Some(sched.to_string()),
+            nr_requests: None,
+            rq_affinity: Some(2),
+            nomerges: Some(nomerges),
+        })
+    }
+
+    fn cpu_rule(&mut self, snap: &Snapshot) -> Option<Action> {
+        let psi_some = snap.psi.as_ref().map(|p| p.some_avg10).unwrap_or(0.0);
+        let hot = psi_some >= self.cfg.psi_cpu_spread && snap.runq_ewma_us_mean >= self.cfg.runq_spread_us;
+        if !hot || self.spread || snap.threads < 2 { return None; }
+        self.spread = true;
+        let width = snap.threads.clamp(1, snap.total_cpus.max(1));
+        Some(Action::SpreadAcrossNUMA { width })
+    }
+
+    /// Under CPU pressure, lower the weight of the busiest neighbour instead of boosting the target.
+    /// A demoted neighbour that has calmed down gets its original weight back first.
+    fn neighbor_rule(&mut self, snap: &Snapshot) -> Option<Action> {
+        self.demoted.retain(|p| std::path::Path::new(p).exists());
+        // a neighbour missing from the snapshot fell out of the busiest few, so it is calm too
+        let calm = self.demoted.iter().position(|p| snap.neighbors.iter()
+            .find(|n| &n.path == p).map(|n| n.cpu_pct < self.cfg.neighbor_cpu_pct).unwrap_or(true));
+        if let Some(i) = calm {
+            let cgroup = self.demoted.remove(i);
+            return Some(Action::Scoped { cgroup, action: Box::new(Action::RestoreCpuWeight) });
+        }
+        let psi_some = snap.psi.as_ref().map(|p| p.some_avg10).unwrap_or(0.0);
+        if psi_some < self.cfg.psi_cpu_spread { return None; }
+        let n = snap.neighbors.iter()
+            .find(|n| n.touchable && n.cpu_pct >= self.cfg.neighbor_cpu_pct && !self.demoted.contains(&n.path))?;
+        self.demoted.push(n.path.clone());
+        Some(Action::Scoped {
+            cgroup: n.path.clone(),
+            action: Box::new(Action::SetCpuWeight { weight: self.cfg.neighbor_weight }),
+        })
+    }
+
+    /// Target keeps major-faulting: drop cold page cache of the neighbour holding the most memory.
+    /// Re-issued per neighbour once the cold window has passed, since the first pass only
+    /// records residency to compare against.
+    fn cache_rule(&mut self, snap: &Snapshot) -> Option<Action> {
+        if snap.prefetch.majflt < self.cfg.majflt_evict { return None; }
+        let rearm = Duration::from_secs(crate::actions::cache::cold_secs());
+        let n = snap.neighbors.iter()
+            .filter(|n| n.touchable && self.evicted.get(&n.path).map(|t| t.elapsed() >= rearm).unwrap_or(true))
+            .max_by_key(|n| n.mem_current)?;
+        self.evicted.insert(n.path.clone(), Instant::now());
+        Some(Action::EvictCache { cgroup: n.path.clone(), max_bytes: self.cfg.evict_bytes })
+    }
+
+    /// Large anonymous footprint mostly on small pages: collapse the hottest VMAs once,
+    /// re-armed when the huge-page share recovers.
+    fn thp_rule(&mut self, snap: &Snapshot) -> Option<Action> {
+        let thp = snap.thp.as_ref()?;
+        if thp.anon_kb < self.cfg.thp_min_anon_kb { return None; }
+        if thp.huge_ratio >= self.cfg.thp_ratio_lo { self.collapsed = false; return None; }
+        if self.collapsed { return None; }
+        self.collapsed = true;
+        Some(Action::CollapseThp { max_vmas: self.cfg.thp_max_vmas })
+    }
+
+    fn mem_rule(&mut self, snap: &Snapshot) -> Option<Action> {
+        let mem = snap.mem.as_ref()?;
+        let psi_some = snap.psi_mem.as_ref().map(|p| p.some_avg10).unwrap_or(0.0);
+        if psi_some < self.cfg.psi_mem_protect {
+            // pressure gone: re-arm once it drops well below the threshold
+            if psi_some < self.cfg.psi_mem_protect / 2.0 { self.protected = false; }
+            return None;
+        }
+        if self.protected { return None; }
+        self.protected = true;
+        Some(Action::ProtectMemory { low: Some(mem.working_set), min: None })
+    }
+}
+
+impl Strategy for Heuristic {
+    fn tick(&mut self, snap: &Snapshot) -> Vec<Action> {
+        let mut out = Vec::new();
+        out.extend(self.neighbor_rule(snap));
+        out.extend(self.cpu_rule(snap));
+        out.extend(self.io_rule(snap));
+        out.extend(self.mem_rule(snap));
+        out.extend(self.cache_rule(snap));
+        out.extend(self.thp_rule(snap));
+        out
+    }
+    fn name(&self) -> &'static str { "heuristic" }
+}
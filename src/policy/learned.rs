@@ -1,64 +1,49 @@
 // src/policy/learned.rs
 
 use crate::metrics::Event;
-use crate::actions::prefetch::{PrefetchAction, PrefetchBackend};
-
-use std::collections::{HashMap, VecDeque};
+use super::prefetch::PrefetchModel;
 
 const LEARNED_DEBUG: bool = true;
 
-#[derive(Default)]
-struct SeqState { hist: VecDeque<u64> }
 
-#[derive(Default)]
-struct PrefetchModel {
-    files: HashMap<(u32,u64,u64), SeqState>, // (tgid, dev, ino) -> history of pgoff
-}
+use crate::{actions::Action, metrics::Snapshot, bandit::LinUcb, hist::HistSummary};
+use super::Strategy;
+use std::time::{Duration, Instant};
 
-impl PrefetchModel {
-    fn on_fault(&mut self, tgid: u32, dev: u64, ino: u64, pgoff: u64) -> Option<PrefetchAction> {
-        let key = (tgid, dev, ino);
-        let st = self.files.entry(key).or_default();
-        if st.hist.len() >= 32 { st.hist.pop_front(); }
-        st.hist.push_back(pgoff);
-        // simple stride detection on last 6 deltas
-        if st.hist.len() < 7 { return None; }
-        let deltas: Vec<u64> = st.hist.iter().copied().collect::<Vec<_>>().windows(2).map(|w| w[1].wrapping_sub(w[0])).collect();
-        let n = deltas.len();
-        let recent = &deltas[n-6..];
-        let candidate = {
-            let mut freq = std::collections::HashMap::<u64,usize>::new();
-            for &d in recent { *freq.entry(d).or_default() += 1; }
-            freq.into_iter().max_by_key(|(_,c)| *c).map(|(d,_)| d).unwrap_or(0)
-        };
-        if candidate == 0 { return None; }
-        let agree = recent.iter().filter(|&&d| d == candidate).count();
-        if agree < 5 { return None; } // strong agreement
-        // Plan next 8 pages into 128KB chunks
-        let page = 4096u64;
-        let mut ranges = Vec::<(u64,u64)>::new();
-        for k in 1..=8 {
-            let off = (pgoff.wrapping_add(candidate*saturating_as_u64(k))) * page;
-            // push 128KB per page predicted (coalesce later if needed)
-            let len = 128*1024;
-            ranges.push((off, len));
+/// Which latency statistic the score is computed from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Objective { Mean, P50, P90, P99 }
+
+impl Objective {
+    pub fn from_env() -> Self {
+        match std::env::var("AGENT_OBJECTIVE").unwrap_or_default().to_ascii_lowercase().as_str() {
+            "p50" => Objective::P50,
+            "p90" => Objective::P90,
+            "p99" => Objective::P99,
+            _ => Objective::Mean,
         }
-        Some(PrefetchAction { tgid, dev, ino, ranges, backend: PrefetchBackend::Fadvise })
     }
-}
-fn saturating_as_u64(x: usize) -> u64 { x as u64 }
 
-use crate::{actions::Action, metrics::Snapshot, bandit::LinUcb};
-use super::Strategy;
-use std::time::{Duration, Instant};
+    /// Falls back to the mean when the interval histogram is empty.
+    fn pick(&self, mean: f64, h: &HistSummary) -> f64 {
+        if h.count == 0 { return mean; }
+        match self {
+            Objective::Mean => mean,
+            Objective::P50 => h.p50_us,
+            Objective::P90 => h.p90_us,
+            Objective::P99 => h.p99_us,
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct LearnedCfg {
+    pub objective: Objective,
     pub epsilon: f64,
     pub min_threads_for_numa: usize,
     pub allow_cpu_weight: bool,
     pub smooth_alpha: f64,
-    pub enabled_arms: [bool; 5], // 0..=4
+    pub enabled_arms: [bool; 8], // 0..=7
 
 
 }
@@ -66,11 +51,12 @@ pub struct LearnedCfg {
 impl Default for LearnedCfg {
     fn default() -> Self {
         Self {
+            objective: Objective::from_env(),
             epsilon: 0.05,
             min_threads_for_numa: 2,
             allow_cpu_weight: true,
             smooth_alpha: 0.2,
-            enabled_arms: [true, true, true, true, true], // 0,3,4 enabled
+            enabled_arms: [true, true, true, true, true, true, true, true],
         }
     }
 }
@@ -109,8 +95,9 @@ impl Learned {
 
     pub fn with_cfg(cfg: LearnedCfg) -> Self {
            // prefetch: PrefetchModel::default(),
-        // Arms: 0=Noop, 1=CpuWeight160, 2=Nice-1, 3=CompactNUMA, 4=SpreadNUMA
-        let bandit = LinUcb::new(5, 4, 0.75);
+        // Arms: 0=Noop, 1=CpuWeight160, 2=Nice-1, 3=CompactNUMA, 4=SpreadNUMA, 5=TuneBlockDev, 6=ProtectMemory,
+        //       7=DeprioritizeNeighbor
+        let bandit = LinUcb::new(8, 6, 0.75);
         Self {
             prefetch: PrefetchModel::default(),
             bandit,
@@ -139,7 +126,9 @@ impl Learned {
         let over = if s.total_cpus > 0 { (s.threads as f64)/(s.total_cpus as f64) } else { 0.0 };
         let over_n = over.clamp(0.0, 1.0);
         let runq_n = (runq / 1.0e5).clamp(0.0, 1.0);
-        vec![1.0, runq_n, fut_share, over_n] // [bias, cpu pressure, futex mix, oversub]
+        let io_seq = s.io.as_ref().map(|io| io.seq_ratio).unwrap_or(0.0).clamp(0.0, 1.0);
+        let mem_p = s.psi_mem.as_ref().map(|m| m.some_avg10 / 100.0).unwrap_or(0.0).clamp(0.0, 1.0);
+        vec![1.0, runq_n, fut_share, over_n, io_seq, mem_p] // [bias, cpu pressure, futex mix, oversub, io sequentiality, mem pressure]
     }
 
     fn score(runq: f64, futex: f64) -> f64 {
@@ -159,10 +148,41 @@ impl Learned {
                 let width = (snap.threads as usize).clamp(1, snap.total_cpus.max(1));
                 vec![Action::SpreadAcrossNUMA { width }]
             }
+            5 => {
+                // sequential streams want deep readahead; random I/O wants shallow readahead and no merging work
+                let Some(io) = snap.io.as_ref() else { return Vec::new() };
+                let (ra, sched, nomerges) = if io.seq_ratio >= 0.6 {
+                    (2048, "mq-deadline", 0)
+                } else {
+                    (64, "none", 1)
+                };
+                vec![Action::TuneBlockDev {
+                    dev: io.dev.clone(),
+                    readahead_kb: Some(ra),
+                    scheduler: Some(sched.to_string()),
+                    nr_requests: None,
+                    rq_affinity: Some(2),
+                    nomerges: Some(nomerges),
+                }]
+            }
+            6 => {
+                // shield the target's working set from reclaim driven by neighbours
+                let Some(mem) = snap.mem.as_ref() else { return Vec::new() };
+                vec![Action::ProtectMemory { low: Some(mem.working_set), min: None }]
+            }
+            7 => {
+                let Some(n) = Self::top_aggressor(snap) else { return Vec::new() };
+                vec![Action::Scoped { cgroup: n.path.clone(), action: Box::new(Action::SetCpuWeight { weight: 50 }) }]
+            }
             _ => Vec::new(),
         }
     }
 
+    /// Busiest neighbour we are allowed to touch, if it is using a meaningful share of CPU.
+    fn top_aggressor(snap: &Snapshot) -> Option<&crate::neighbors::Neighbor> {
+        snap.neighbors.iter().find(|n| n.touchable && n.cpu_pct >= 5.0)
+    }
+
     fn choose_arm(&self, x: &Vec<f64>, allowed: &[usize]) -> usize {
         if self.cfg.epsilon > 0.0 && !allowed.is_empty() {
             let nanos = std::time::SystemTime::now()
@@ -180,10 +200,14 @@ impl Learned {
 
 impl Strategy for Learned {
     fn tick(&mut self, snap: &Snapshot) -> Vec<Action> {
+        self.prefetch.tick();
         let a = self.cfg.smooth_alpha;
 
-        self.sm_runq = a * snap.runq_ewma_us_mean.max(0.0) + (1.0 - a) * self.sm_runq;
-        self.sm_futex = a * snap.futex_ewma_us_mean.max(0.0) + (1.0 - a) * self.sm_futex;
+        let obj = self.cfg.objective;
+        let runq_in = obj.pick(snap.runq_ewma_us_mean, &snap.lat.runq);
+        let futex_in = obj.pick(snap.futex_ewma_us_mean, &snap.lat.futex);
+        self.sm_runq = a * runq_in.max(0.0) + (1.0 - a) * self.sm_runq;
+        self.sm_futex = a * futex_in.max(0.0) + (1.0 - a) * self.sm_futex;
 
         let (psi_some10, psi_full10) = if let Some(ref psi) = snap.psi {
             (psi.some_avg10 / 100.0, psi.full_avg10 / 100.0)
@@ -204,7 +228,10 @@ impl Strategy for Learned {
             + 0.5 * psi_some10
             + 1.0 * psi_full10
             + 0.7 * psi_mem_some10    
-            + 1.3 * psi_mem_full10;   
+            + 1.3 * psi_mem_full10
+            + 0.5 * snap.cpu_stat.map(|c| c.throttled_ratio()).unwrap_or(0.0)
+            // network tail: retransmit pressure and RTT p99 in ms
+            + snap.net.as_ref().map(|n| 2.0 * n.retrans_ratio() + 0.1 * n.rtt.p99_us / 1000.0).unwrap_or(0.0);
 
         for p in self.pending.iter_mut() {
             if p.due > 0 { p.due -= 1; }
@@ -234,13 +261,22 @@ impl Strategy for Learned {
 
         if self.cfg.enabled_arms[2] { allowed.push(2); }
 
+        if self.cfg.enabled_arms[7] && psi_some10 > 0.01 && Self::top_aggressor(snap).is_some() {
+            allowed.push(7);
+        }
+
         if snap.total_cpus >= 2 && snap.threads >= self.cfg.min_threads_for_numa {
             if self.cfg.enabled_arms[3] { allowed.push(3); }
             if self.cfg.enabled_arms[4] { allowed.push(4); }
         }
 
+        if self.cfg.enabled_arms[5] && snap.io.as_ref().map(|io| io.reqs > 0).unwrap_or(false) {
+            allowed.push(5);
+        }
+
         if psi_mem_some10 > 0.005 || psi_mem_full10 > 0.0005 {
             allowed.retain(|&a| a != 4 /* SpreadAcrossNUMA */);
+            if self.cfg.enabled_arms[6] && snap.mem.is_some() { allowed.push(6); }
         }
 
         let now = Instant::now();
@@ -297,8 +333,8 @@ impl Strategy for Learned {
     }
     fn on_event(&mut self, evt: &crate::metrics::Event) -> Option<crate::actions::Action> {
         if LEARNED_DEBUG { eprintln!("[learned] on_event"); }
-        if let crate::metrics::Event::PrefetchFault { tgid, dev, ino, pgoff, ts_ns } = *evt { if LEARNED_DEBUG { eprintln!("[learned] prefetch fault tgid={} dev={} ino={} pgoff={} ts={}", tgid, dev, ino, pgoff, ts_ns); }
-            if let Some(a) = self.prefetch.on_fault(tgid, dev, ino, pgoff) {
+        if let crate::metrics::Event::PrefetchFault { tgid, pid, dev, ino, pgoff, ts_ns } = *evt { if LEARNED_DEBUG { eprintln!("[learned] prefetch fault tgid={} pid={} dev={} ino={} pgoff={} ts={}", tgid, pid, dev, ino, pgoff, ts_ns); }
+            if let Some(a) = self.prefetch.on_fault(tgid, pid, dev, ino, pgoff, ts_ns) {
                 return Some(crate::actions::Action::Prefetch(a));
             }
         }
@@ -7,4 +7,12 @@ pub trait Strategy: Send {
     fn name(&self) -> &'static str;
 }
 
-pub mod learned;
\ No newline at end of file
+impl Strategy for Box<dyn Strategy> {
+    fn tick(&mut self, snap: &Snapshot) -> Vec<Action> { (**self).tick(snap) }
+    fn on_event(&mut self, evt: &crate::metrics::Event) -> Option<Action> { (**self).on_event(evt) }
+    fn name(&self) -> &'static str { (**self).name() }
+}
+
+pub mod learned;
+pub mod heuristic;
+pub mod prefetch;
\ No newline at end of file
@@ -0,0 +1,241 @@
+// src/policy/prefetch.rs
+use crate::actions::prefetch::{self, PrefetchAction, PrefetchBackend};
+use std::collections::{HashMap, VecDeque};
+
+const PAGE: u64 = 4096;
+const HIST_DELTAS: usize = 16;
+const MAX_PERIOD: usize = 4;
+const MIN_DEPTH: u64 = 2;
+const MAX_DEPTH: u64 = 256;
+const INIT_DEPTH: u64 = 8;
+const MAX_STREAMS: usize = 1024;
+// a prefetched page not faulted within this window counts as waste
+const HIT_WINDOW_NS: u64 = 2_000_000_000;
+const STREAM_IDLE_NS: u64 = 30_000_000_000;
+// residency is sampled per aligned window of this many pages, once per tick
+const RESIDENCY_WINDOW: u64 = 512;
+
+/// CLOCK_MONOTONIC, the clock of bpf_ktime_get_ns() and so of event timestamps.
+fn monotonic_ns() -> u64 {
+    let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts); }
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+/// One thread's access stream over one file.
+struct Stream {
+    last: u64,
+    deltas: VecDeque<i64>,
+    depth: u64,
+    hits: u64,
+    waste: u64,
+    last_ts: u64,
+}
+
+impl Stream {
+    fn new(pgoff: u64, ts: u64) -> Self {
+        Self { last: pgoff, deltas: VecDeque::new(), depth: INIT_DEPTH, hits: 0, waste: 0, last_ts: ts }
+    }
+
+    /// Smallest period p such that the recent deltas repeat every p steps. A plain
+    /// stride is p = 1; interleaved strides (+1,+64,+1,+64...) show up as p > 1.
+    fn cycle(&self) -> Option<Vec<i64>> {
+        let d: Vec<i64> = self.deltas.iter().copied().collect();
+        for p in 1..=MAX_PERIOD {
+            let window = (3 * p).max(6);
+            if d.len() < window + p { continue; }
+            let tail = &d[d.len() - window - p..];
+            if (p..tail.len()).all(|i| tail[i] == tail[i - p]) {
+                let cyc = d[d.len() - p..].to_vec();
+                if cyc.iter().all(|&x| x == 0) { return None; }
+                return Some(cyc);
+            }
+        }
+        None
+    }
+
+    /// Grow depth while prefetches are used, shrink it on waste.
+    fn adapt(&mut self) {
+        let resolved = self.hits + self.waste;
+        if resolved < self.depth { return; }
+        let ratio = self.hits as f64 / resolved as f64;
+        if ratio >= 0.75 {
+            self.depth = (self.depth * 2).min(MAX_DEPTH);
+        } else if ratio < 0.25 {
+            self.depth = (self.depth / 2).max(MIN_DEPTH);
+        }
+        self.hits = 0;
+        self.waste = 0;
+    }
+}
+
+/// (tgid, dev, ino) -> pgoff -> (predicting pid, ts_ns)
+type PageMap = HashMap<(u32, u64, u64), HashMap<u64, (u32, u64)>>;
+
+/// Per-thread stride prediction over filemap_fault events, with hit/waste feedback.
+#[derive(Default)]
+pub(crate) struct PrefetchModel {
+    streams: HashMap<(u32, u32, u64, u64), Stream>, // (tgid, pid, dev, ino)
+    // pages predicted this tick, not yet known to be issued
+    proposed: PageMap,
+    // pages issued but not yet faulted
+    outstanding: PageMap,
+    // (dev, ino, window) -> resident pages of the window, cut short at EOF; cleared every tick
+    residency: HashMap<(u64, u64, u64), Option<Vec<bool>>>,
+}
+
+impl PrefetchModel {
+    /// Once per tick: promote issued proposals to outstanding, expire stale ones as waste,
+    /// forget idle streams and drop the residency cache.
+    pub fn tick(&mut self) {
+        let mut proposed = std::mem::take(&mut self.proposed);
+        for is in prefetch::take_issued() {
+            let key = (is.tgid, is.dev, is.ino);
+            let Some(props) = proposed.get_mut(&key) else { continue };
+            let out = self.outstanding.entry(key).or_default();
+            for p in is.pages {
+                if let Some(v) = props.remove(&p) { out.insert(p, v); }
+            }
+        }
+        // what is left was trimmed by the budget or failed: neither a hit nor waste
+        self.residency.clear();
+        self.expire(monotonic_ns());
+    }
+
+    /// Expire outstanding pages older than the hit window, charging them as waste.
+    fn expire(&mut self, now: u64) {
+        let mut wasted: HashMap<(u32, u32, u64, u64), u64> = HashMap::new();
+        self.outstanding.retain(|&(tgid, dev, ino), pages| {
+            pages.retain(|_, &mut (pid, ts)| {
+                let live = now.saturating_sub(ts) < HIT_WINDOW_NS;
+                if !live { *wasted.entry((tgid, pid, dev, ino)).or_default() += 1; }
+                live
+            });
+            !pages.is_empty()
+        });
+        for ((tgid, pid, dev, ino), n) in wasted {
+            prefetch::record_waste(tgid, dev, ino, n);
+            if let Some(s) = self.streams.get_mut(&(tgid, pid, dev, ino)) { s.waste += n; s.adapt(); }
+        }
+        self.streams.retain(|_, s| now.saturating_sub(s.last_ts) < STREAM_IDLE_NS);
+        if self.streams.len() > MAX_STREAMS {
+            let mut ts: Vec<u64> = self.streams.values().map(|s| s.last_ts).collect();
+            ts.sort_unstable();
+            let cut = ts[self.streams.len() - MAX_STREAMS];
+            self.streams.retain(|_, s| s.last_ts >= cut);
+        }
+    }
+
+    /// Whether page `p` is worth requesting: not already cached and not past EOF.
+    /// Unknown residency (unresolvable file) counts as worth it.
+    fn worth_fetching(&mut self, tgid: u32, dev: u64, ino: u64, p: u64) -> bool {
+        let win = p / RESIDENCY_WINDOW;
+        let res = self.residency.entry((dev, ino, win))
+            .or_insert_with(|| prefetch::residency(tgid, dev, ino, win * RESIDENCY_WINDOW, RESIDENCY_WINDOW));
+        match res {
+            Some(r) => r.get((p % RESIDENCY_WINDOW) as usize).map(|&cached| !cached).unwrap_or(false),
+            None => true,
+        }
+    }
+
+    pub fn on_fault(&mut self, tgid: u32, pid: u32, dev: u64, ino: u64, pgoff: u64, ts_ns: u64) -> Option<PrefetchAction> {
+        let fkey = (tgid, dev, ino);
+
+        // feedback: was this page one we asked for?
+        if let Some((issuer, issued_ts)) = self.outstanding.get_mut(&fkey).and_then(|p| p.remove(&pgoff)) {
+            prefetch::record_hit(tgid, dev, ino, ts_ns.saturating_sub(issued_ts));
+            if let Some(s) = self.streams.get_mut(&(tgid, issuer, dev, ino)) { s.hits += 1; s.adapt(); }
+        }
+
+        let st = self.streams.entry((tgid, pid, dev, ino)).or_insert_with(|| Stream::new(pgoff, ts_ns));
+        if st.last_ts != ts_ns || st.last != pgoff {
+            if st.deltas.len() >= HIST_DELTAS { st.deltas.pop_front(); }
+            st.deltas.push_back(pgoff as i64 - st.last as i64);
+        }
+        st.last = pgoff;
+        st.last_ts = ts_ns;
+
+        let cyc = st.cycle()?;
+        let depth = st.depth;
+        let mut pages = Vec::new();
+        let mut cur = pgoff as i64;
+        for k in 0..depth as usize {
+            cur += cyc[k % cyc.len()];
+            if cur < 0 { break; }
+            let p = cur as u64;
+            let known = self.outstanding.get(&fkey).map(|m| m.contains_key(&p)).unwrap_or(false)
+                || self.proposed.get(&fkey).map(|m| m.contains_key(&p)).unwrap_or(false);
+            if known || pages.contains(&p) { continue; }
+            // already-resident pages would be neither a hit nor waste
+            if self.worth_fetching(tgid, dev, ino, p) { pages.push(p); }
+        }
+        if pages.is_empty() { return None; }
+        let props = self.proposed.entry(fkey).or_default();
+        for &p in &pages { props.insert(p, (pid, ts_ns)); }
+        Some(PrefetchAction { tgid, dev, ino, ranges: coalesce(pages), backend: PrefetchBackend::preferred() })
+    }
+}
+
+/// Sorted, deduplicated pages to byte ranges, merging adjacent pages.
+fn coalesce(mut pages: Vec<u64>) -> Vec<(u64, u64)> {
+    pages.sort_unstable();
+    pages.dedup();
+    let mut out: Vec<(u64, u64)> = Vec::new();
+    for p in pages {
+        match out.last_mut() {
+            Some((off, len)) if *off + *len == p * PAGE => *len += PAGE,
+            _ => out.push((p * PAGE, PAGE)),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(deltas: &[i64]) -> Stream {
+        let mut s = Stream::new(0, 0);
+        s.deltas.extend(deltas);
+        s
+    }
+
+    #[test]
+    fn cycle_plain_stride() {
+        assert_eq!(stream(&[1; 7]).cycle(), Some(vec![1]));
+        assert_eq!(stream(&[-2; 8]).cycle(), Some(vec![-2]));
+    }
+
+    #[test]
+    fn cycle_needs_enough_history() {
+        assert_eq!(stream(&[1; 6]).cycle(), None);
+        assert_eq!(stream(&[]).cycle(), None);
+    }
+
+    #[test]
+    fn cycle_interleaved_strides() {
+        let d: Vec<i64> = [1, 64].iter().copied().cycle().take(8).collect();
+        assert_eq!(stream(&d).cycle(), Some(vec![1, 64]));
+        // the cycle is returned in the phase of the most recent deltas
+        let d: Vec<i64> = [1, 64].iter().copied().cycle().take(9).collect();
+        assert_eq!(stream(&d).cycle(), Some(vec![64, 1]));
+        let d: Vec<i64> = [1, 1, 1, 100].iter().copied().cycle().take(16).collect();
+        assert_eq!(stream(&d).cycle(), Some(vec![1, 1, 1, 100]));
+    }
+
+    #[test]
+    fn cycle_rejects_noise_and_repeats() {
+        assert_eq!(stream(&[3, 9, 1, 4, 7, 2, 8, 5, 6, 11]).cycle(), None);
+        // the same page faulting again is not a stride
+        assert_eq!(stream(&[0; 10]).cycle(), None);
+        // only the recent tail has to repeat
+        assert_eq!(stream(&[5, 9, 1, 1, 1, 1, 1, 1, 1]).cycle(), Some(vec![1]));
+    }
+
+    #[test]
+    fn coalesce_merges_adjacent_pages() {
+        assert_eq!(coalesce(vec![3, 1, 2, 2, 7, 8, 10]), vec![(PAGE, 3 * PAGE), (7 * PAGE, 2 * PAGE), (10 * PAGE, PAGE)]);
+        assert_eq!(coalesce(vec![0]), vec![(0, PAGE)]);
+        assert!(coalesce(Vec::new()).is_empty());
+    }
+}
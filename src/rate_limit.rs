@@ -61,12 +61,37 @@ pub(crate) fn stable_key(a: &Action) -> String {
     match a {
         Action::SetCpuset { cgroup, cpus } => format!("cpuset:{}:{:?}", cgroup, cpus),
         Action::SetCpuWeight { weight } => format!("cpuweight:{}", weight),
+        Action::RestoreCpuWeight => "cpuweight:restore".to_string(),
         Action::SetNice { prio } => format!("nice:{}", prio),
         Action::SetIoPriority { class, prio } => format!("ioprio:{}:{}", class, prio),
         Action::SetSchedBatch { enable } => format!("sched_batch:{}", enable),
+        Action::SetSchedIdle { enable } => format!("sched_idle:{}", enable),
+        Action::SetTimerSlack { ns } => format!("timerslack:{}", ns),
+        Action::SetTaskUclamp { min, max } => format!("task_uclamp:{:?}:{:?}", min, max),
+        Action::SetLatencyNice { nice } => format!("latency_nice:{}", nice),
         Action::CompactWithinNUMA { node } => format!("plan_compact:{:?}", node),
         Action::SpreadAcrossNUMA { width } => format!("plan_spread:{}", width),
         Action::Prefetch(prefetch_action) => format!("prefetch_action:{:?}", prefetch_action),
+        Action::TuneBlockDev { dev, readahead_kb, scheduler, nr_requests, rq_affinity, nomerges } =>
+            format!("blockdev:{}:{:?}:{:?}:{:?}:{:?}:{:?}", dev, readahead_kb, scheduler, nr_requests, rq_affinity, nomerges),
+        Action::SetIoWeight { weight, dev } => format!("ioweight:{:?}:{}", dev, weight),
+        Action::SetIoMax { cgroup, dev, rbps, wbps, riops, wiops } =>
+            format!("iomax:{}:{}:{:?}:{:?}:{:?}:{:?}", cgroup, dev, rbps, wbps, riops, wiops),
+        Action::SetIoLatency { dev, target_us } => format!("iolatency:{}:{}", dev, target_us),
+        Action::SetMemoryHigh { cgroup, bytes } => format!("memhigh:{}:{:?}", cgroup, bytes),
+        Action::ProtectMemory { low, min } => format!("memprotect:{:?}:{:?}", low, min),
+        Action::SetSwapMax { cgroup, bytes } => format!("swapmax:{}:{:?}", cgroup, bytes),
+        Action::ReclaimMemory { cgroup, .. } => format!("memreclaim:{}", cgroup),
+        Action::SetCpuMax { cgroup, quota_us, period_us } => format!("cpumax:{}:{:?}:{}", cgroup, quota_us, period_us),
+        Action::SetCpuUclamp { min_pct, max_pct } => format!("uclamp:{:?}:{:?}", min_pct, max_pct),
+        Action::Scoped { cgroup, action } => format!("scoped:{}:{}", cgroup, stable_key(action)),
+        Action::PromoteRt { threads } => format!("promote_rt:{:?}", threads.iter().map(|t| t.0).collect::<Vec<_>>()),
+        Action::Threads { comm, action } => format!("threads:{}:{}", comm, stable_key(action)),
+        Action::MigrateMemory { to_node, .. } => format!("migrate:{}", to_node),
+        Action::SetAffinity { cgroup, cpus } => format!("affinity:{}:{:?}", cgroup, cpus),
+        Action::CollapseThp { .. } => "thp_collapse".to_string(),
+        Action::EvictCache { cgroup, .. } => format!("evict_cache:{}", cgroup),
+        Action::SteerNet { iface, cpus, steer } => format!("steer_net:{}:{:?}:{:?}", iface, cpus, steer),
     }
 }
 
@@ -80,12 +105,35 @@ pub fn log_tick(mut file: &std::fs::File, snap: &Snapshot, actions: &[Action], s
         let k = match a {
             Action::SetCpuset { .. } => "SetCpuset",
             Action::SetCpuWeight { .. } => "SetCpuWeight",
+            Action::RestoreCpuWeight => "RestoreCpuWeight",
             Action::SetNice { .. } => "SetNice",
             Action::SetIoPriority { .. } => "SetIoPriority",
             Action::SetSchedBatch { .. } => "SetSchedBatch",
+            Action::SetSchedIdle { .. } => "SetSchedIdle",
+            Action::SetTimerSlack { .. } => "SetTimerSlack",
+            Action::SetTaskUclamp { .. } => "SetTaskUclamp",
+            Action::SetLatencyNice { .. } => "SetLatencyNice",
             Action::CompactWithinNUMA { .. } => "PlanCompact",
             Action::SpreadAcrossNUMA { .. } => "PlanSpread",
             Action::Prefetch(prefetch_action) => "Prefetch",
+            Action::TuneBlockDev { .. } => "TuneBlockDev",
+            Action::SetIoWeight { .. } => "SetIoWeight",
+            Action::SetIoMax { .. } => "SetIoMax",
+            Action::SetIoLatency { .. } => "SetIoLatency",
+            Action::SetMemoryHigh { .. } => "SetMemoryHigh",
+            Action::ProtectMemory { .. } => "ProtectMemory",
+            Action::SetSwapMax { .. } => "SetSwapMax",
+            Action::ReclaimMemory { .. } => "ReclaimMemory",
+            Action::SetCpuMax { .. } => "SetCpuMax",
+            Action::SetCpuUclamp { .. } => "SetCpuUclamp",
+            Action::Scoped { .. } => "Scoped",
+            Action::Threads { .. } => "Threads",
+            Action::PromoteRt { .. } => "PromoteRt",
+            Action::MigrateMemory { .. } => "MigrateMemory",
+            Action::SetAffinity { .. } => "SetAffinity",
+            Action::CollapseThp { .. } => "CollapseThp",
+            Action::EvictCache { .. } => "EvictCache",
+            Action::SteerNet { .. } => "SteerNet",
         };
         *kinds.entry(k).or_default() += 1;
     }
@@ -96,7 +144,7 @@ pub fn log_tick(mut file: &std::fs::File, snap: &Snapshot, actions: &[Action], s
         .join(",");
 
     let line = format!(
-        r#"{{"ts":{}.{},"strategy":"{}","threads":{},"runq_ewma_us":{:.0},"futex_ewma_us":{:.0},"gate":"{}","actions":{{{}}}}}"#,
+        r#"{{"ts":{}.{},"strategy":"{}","threads":{},"runq_ewma_us":{:.0},"futex_ewma_us":{:.0},"gate":"{}","actions":{{{}}},"prefetch":{{"bytes":{},"over_budget":{},"hits":{},"waste":{},"hit_rate":{:.2},"lead_p50_us":{:.0},"majflt":{},"events_dropped":{}}}}}"#,
 
         ts.as_secs(),
         ts.subsec_nanos(),
@@ -105,7 +153,15 @@ pub fn log_tick(mut file: &std::fs::File, snap: &Snapshot, actions: &[Action], s
         snap.runq_ewma_us_mean,
         snap.futex_ewma_us_mean,
         gate_reason,
-        kinds_str
+        kinds_str,
+        snap.prefetch.bytes_requested,
+        snap.prefetch.bytes_over_budget,
+        snap.prefetch.hits,
+        snap.prefetch.waste,
+        snap.prefetch.hit_rate,
+        snap.prefetch.lead.p50_us,
+        snap.prefetch.majflt,
+        snap.prefetch_events.dropped(),
     );
 
     let _ = file.write_all(line.as_bytes());
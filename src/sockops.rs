@@ -0,0 +1,112 @@
+// src/sockops.rs
+//! TCP socket policy pushed to sockops.bpf.c through SOCK_RULES_MAP.
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::time::SystemTime;
+
+/// Must match SR_* and SOCK_RULES in bpf/sockops.bpf.c
+const SR_ENABLED: u32 = 1 << 0;
+const SR_CC: u32 = 1 << 1;
+const SR_KEEPALIVE: u32 = 1 << 2;
+const SR_NOTSENT: u32 = 1 << 3;
+const SR_SNDBUF: u32 = 1 << 4;
+const SR_RCVBUF: u32 = 1 << 5;
+const SR_IW: u32 = 1 << 6;
+const SR_RTO: u32 = 1 << 7;
+pub const SOCK_RULES: usize = 8;
+const CC_NAME_MAX: usize = 16;
+
+/// One rule as read from the AGENT_SOCKOPS_CONFIG JSON array; unset fields are left alone.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SockRule {
+    pub local_port: u16,
+    pub remote_port: u16,
+    pub cc: Option<String>,
+    /// (idle_s, intvl_s, cnt); zeros keep the system value
+    pub keepalive: Option<(u32, u32, u32)>,
+    pub notsent_lowat: Option<u32>,
+    pub sndbuf: Option<u32>,
+    pub rcvbuf: Option<u32>,
+    pub init_cwnd: Option<u32>,
+    pub rto_init_ms: Option<u32>,
+}
+
+/// Layout of struct sock_rule.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct RawRule {
+    flags: u32,
+    local_port: u16,
+    remote_port: u16,
+    cc: [u8; CC_NAME_MAX],
+    keep_idle: u32,
+    keep_intvl: u32,
+    keep_cnt: u32,
+    notsent_lowat: u32,
+    sndbuf: u32,
+    rcvbuf: u32,
+    init_cwnd: u32,
+    rto_init_ms: u32,
+}
+
+impl SockRule {
+    /// What the program did before it was configurable: BBR plus keepalive 30/10/6 on every socket.
+    pub fn legacy() -> Self {
+        Self { cc: Some("bbr".into()), keepalive: Some((30, 10, 6)), ..Default::default() }
+    }
+
+    fn raw(&self) -> RawRule {
+        let mut r = RawRule {
+            flags: SR_ENABLED,
+            local_port: self.local_port,
+            remote_port: self.remote_port,
+            ..Default::default()
+        };
+        if let Some(cc) = &self.cc {
+            let b = cc.as_bytes();
+            let n = b.len().min(CC_NAME_MAX - 1);
+            r.cc[..n].copy_from_slice(&b[..n]);
+            r.flags |= SR_CC;
+        }
+        if let Some((idle, intvl, cnt)) = self.keepalive {
+            (r.keep_idle, r.keep_intvl, r.keep_cnt) = (idle, intvl, cnt);
+            r.flags |= SR_KEEPALIVE;
+        }
+        if let Some(v) = self.notsent_lowat { r.notsent_lowat = v; r.flags |= SR_NOTSENT; }
+        if let Some(v) = self.sndbuf { r.sndbuf = v; r.flags |= SR_SNDBUF; }
+        if let Some(v) = self.rcvbuf { r.rcvbuf = v; r.flags |= SR_RCVBUF; }
+        if let Some(v) = self.init_cwnd { r.init_cwnd = v; r.flags |= SR_IW; }
+        if let Some(v) = self.rto_init_ms { r.rto_init_ms = v; r.flags |= SR_RTO; }
+        r
+    }
+}
+
+/// Map values for slots 0..SOCK_RULES; unused slots are disabled.
+pub fn encode(rules: &[SockRule]) -> Vec<Vec<u8>> {
+    if rules.len() > SOCK_RULES {
+        eprintln!("[agent] sockops: only the first {} of {} rules are used", SOCK_RULES, rules.len());
+    }
+    (0..SOCK_RULES).map(|i| {
+        let raw = rules.get(i).map(|r| r.raw()).unwrap_or_default();
+        // SAFETY: RawRule is repr(C) plain data
+        unsafe {
+            std::slice::from_raw_parts(&raw as *const RawRule as *const u8, std::mem::size_of::<RawRule>()).to_vec()
+        }
+    }).collect()
+}
+
+pub fn config_path() -> Option<String> {
+    std::env::var("AGENT_SOCKOPS_CONFIG").ok().filter(|p| !p.is_empty())
+}
+
+/// Rules from AGENT_SOCKOPS_CONFIG, or the legacy catch-all when it is unset.
+pub fn load_rules() -> Result<Vec<SockRule>> {
+    let Some(path) = config_path() else { return Ok(vec![SockRule::legacy()]) };
+    let s = std::fs::read_to_string(&path).with_context(|| format!("read {}", path))?;
+    serde_json::from_str(&s).with_context(|| format!("parse {}", path))
+}
+
+pub fn config_mtime() -> Option<SystemTime> {
+    std::fs::metadata(config_path()?).and_then(|m| m.modified()).ok()
+}
@@ -1,4 +1,10 @@
 // src/topology.rs
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{collections::BTreeMap, fs, path::{Path, PathBuf}};
+
+pub const SYSFS_CPU: &str = "/sys/devices/system/cpu";
+
 pub fn to_cpuset_list(cpus: &[usize]) -> String {
     let mut v = cpus.to_vec();
     v.sort_unstable(); v.dedup();
@@ -14,4 +20,304 @@ pub fn to_cpuset_list(cpus: &[usize]) -> String {
         i = j+1;
     }
     out
-}
\ No newline at end of file
+}
+
+pub fn parse_cpu_list(s: &str) -> Vec<usize> {
+    let mut out = Vec::new();
+    for part in s.trim().split(',') {
+        if let Some((a,b)) = part.split_once('-') {
+            if let (Ok(a), Ok(b)) = (a.trim().parse::<usize>(), b.trim().parse::<usize>()) {
+                for x in a.min(b)..=a.max(b) { out.push(x); }
+            }
+        } else if let Ok(v) = part.trim().parse::<usize>() {
+            out.push(v);
+        }
+    }
+    out.sort_unstable();
+    out.dedup();
+    out
+}
+
+/// Online CPU ids, which may have gaps; falls back to 0..nproc when sysfs is unreadable.
+pub fn online_cpus() -> Vec<usize> {
+    match fs::read_to_string(Path::new(SYSFS_CPU).join("online")) {
+        Ok(s) if !parse_cpu_list(&s).is_empty() => parse_cpu_list(&s),
+        _ => (0..num_cpus::get()).collect(),
+    }
+}
+
+/// How placement treats shared hardware.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum Placement {
+    /// NUMA nodes only (legacy behaviour).
+    Node,
+    /// Spread/fill by LLC domain, one thread per core before using siblings.
+    Llc,
+    /// Like Llc but compact packs SMT siblings together to minimise cores used.
+    Smt,
+}
+
+impl Placement {
+    pub fn from_env() -> Self {
+        match std::env::var("AGENT_PLACEMENT").unwrap_or_default().to_ascii_lowercase().as_str() {
+            "node" => Placement::Node,
+            "smt" => Placement::Smt,
+            _ => Placement::Llc,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CpuInfo {
+    pub cpu: usize,
+    pub node: u32,
+    pub package: u32,
+    pub die: u32,
+    pub core: u32,
+    pub siblings: Vec<usize>,
+    /// id of the last-level cache domain (first CPU sharing it when sysfs has no cache id)
+    pub llc: u32,
+    /// relative compute capacity (1024 = biggest core); 1024 when not exposed
+    pub capacity: u32,
+}
+
+impl CpuInfo {
+    /// Lowest-numbered SMT sibling is the "primary" thread of a core.
+    pub fn is_primary(&self) -> bool {
+        self.siblings.first().map(|s| *s == self.cpu).unwrap_or(true)
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Topology {
+    pub cpus: BTreeMap<usize, CpuInfo>,
+}
+
+fn read_u32(p: &Path) -> Option<u32> {
+    fs::read_to_string(p).ok()?.trim().parse::<i64>().ok().map(|v| v.max(0) as u32)
+}
+
+fn cpu_node(dir: &Path) -> u32 {
+    fs::read_dir(dir).ok()
+        .and_then(|rd| rd.flatten().find_map(|e| {
+            e.file_name().to_str()?.strip_prefix("node")?.parse::<u32>().ok()
+        }))
+        .unwrap_or(0)
+}
+
+/// cpu -> node from the `node/node*/cpulist` files next to the cpu directory.
+fn node_cpulists(root: &Path) -> BTreeMap<usize, u32> {
+    let mut m = BTreeMap::new();
+    let Some(dir) = root.parent().map(|p| p.join("node")) else { return m };
+    for e in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let Some(node) = e.file_name().to_str().and_then(|n| n.strip_prefix("node")?.parse::<u32>().ok()) else { continue };
+        let list = fs::read_to_string(e.path().join("cpulist")).unwrap_or_default();
+        for cpu in parse_cpu_list(&list) { m.insert(cpu, node); }
+    }
+    m
+}
+
+/// Id of the highest-level data/unified cache of this CPU.
+fn cpu_llc(dir: &Path, cpu: usize) -> u32 {
+    let mut best: Option<(u32, u32)> = None; // (level, id)
+    if let Ok(rd) = fs::read_dir(dir.join("cache")) {
+        for e in rd.flatten() {
+            let p = e.path();
+            if !e.file_name().to_string_lossy().starts_with("index") { continue; }
+            let ty = fs::read_to_string(p.join("type")).unwrap_or_default();
+            if ty.trim() == "Instruction" { continue; }
+            let Some(level) = read_u32(&p.join("level")) else { continue };
+            let id = read_u32(&p.join("id")).unwrap_or_else(|| {
+                fs::read_to_string(p.join("shared_cpu_list")).ok()
+                    .and_then(|s| parse_cpu_list(&s).first().copied())
+                    .unwrap_or(cpu) as u32
+            });
+            if best.map(|(l, _)| level > l).unwrap_or(true) { best = Some((level, id)); }
+        }
+    }
+    best.map(|(_, id)| id).unwrap_or(0)
+}
+
+impl Topology {
+    pub fn load() -> Result<Self> { Self::from_sysfs(Path::new(SYSFS_CPU)) }
+
+    /// Build from a sysfs cpu directory (`/sys/devices/system/cpu` or a fixture tree).
+    pub fn from_sysfs(root: &Path) -> Result<Self> {
+        let online = fs::read_to_string(root.join("online"))
+            .with_context(|| format!("read {}/online", root.display()))?;
+        let node_of = node_cpulists(root);
+        let mut cpus = BTreeMap::new();
+        for cpu in parse_cpu_list(&online) {
+            let dir: PathBuf = root.join(format!("cpu{}", cpu));
+            let t = dir.join("topology");
+            let siblings = fs::read_to_string(t.join("thread_siblings_list"))
+                .or_else(|_| fs::read_to_string(t.join("core_cpus_list")))
+                .map(|s| parse_cpu_list(&s))
+                .unwrap_or_else(|_| vec![cpu]);
+            cpus.insert(cpu, CpuInfo {
+                cpu,
+                node: node_of.get(&cpu).copied().unwrap_or_else(|| cpu_node(&dir)),
+                package: read_u32(&t.join("physical_package_id")).unwrap_or(0),
+                die: read_u32(&t.join("die_id")).unwrap_or(0),
+                core: read_u32(&t.join("core_id")).unwrap_or(cpu as u32),
+                siblings,
+                llc: cpu_llc(&dir, cpu),
+                capacity: read_u32(&dir.join("cpu_capacity")).unwrap_or(1024),
+            });
+        }
+        Ok(Self { cpus })
+    }
+
+    /// Restrict the model to `allowed` CPUs.
+    pub fn restrict(&self, allowed: &[usize]) -> Self {
+        Self { cpus: self.cpus.iter().filter(|(c, _)| allowed.contains(c)).map(|(c, i)| (*c, i.clone())).collect() }
+    }
+
+    /// node -> cpus, the shape `numa::cpu_topology` returns.
+    pub fn nodes(&self) -> BTreeMap<u32, Vec<usize>> {
+        let mut m: BTreeMap<u32, Vec<usize>> = BTreeMap::new();
+        for c in self.cpus.values() { m.entry(c.node).or_default().push(c.cpu); }
+        m
+    }
+
+    /// (node, llc) -> cpus ordered primaries-first, bigger cores first.
+    pub fn llc_domains(&self) -> BTreeMap<(u32, u32), Vec<usize>> {
+        let mut m: BTreeMap<(u32, u32), Vec<&CpuInfo>> = BTreeMap::new();
+        for c in self.cpus.values() { m.entry((c.node, c.llc)).or_default().push(c); }
+        m.into_iter().map(|(k, mut v)| {
+            v.sort_by_key(|c| (!c.is_primary(), std::cmp::Reverse(c.capacity), c.cpu));
+            (k, v.into_iter().map(|c| c.cpu).collect())
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+impl Topology {
+    /// In-memory machine: `nodes` sockets of `llcs` LLCs of `cores` cores each, `smt` threads
+    /// per core numbered the way x86 does (all primaries first), minus `offline` CPUs.
+    pub(crate) fn synthetic(nodes: u32, llcs: u32, cores: u32, smt: u32, offline: &[usize]) -> Self {
+        let total = (nodes * llcs * cores) as usize;
+        let mut cpus = BTreeMap::new();
+        for c in 0..total {
+            let siblings: Vec<usize> = (0..smt as usize).map(|t| t * total + c).collect();
+            for &cpu in &siblings {
+                if offline.contains(&cpu) { continue; }
+                let llc = c as u32 / cores;
+                cpus.insert(cpu, CpuInfo {
+                    cpu,
+                    node: llc / llcs,
+                    package: llc / llcs,
+                    die: 0,
+                    core: c as u32,
+                    siblings: siblings.clone(),
+                    llc,
+                    capacity: 1024,
+                });
+            }
+        }
+        Self { cpus }
+    }
+}
+
+/// CPUs placement may use: online CPUs within the parent cgroup's cpuset.cpus.effective.
+fn effective_cpus(root: &Path, target_cg: &str) -> (String, String) {
+    let online = fs::read_to_string(root.join("online")).unwrap_or_default().trim().to_string();
+    let parent = Path::new(target_cg).parent().unwrap_or(Path::new("/sys/fs/cgroup"));
+    let eff = fs::read_to_string(parent.join("cpuset.cpus.effective"))
+        .or_else(|_| fs::read_to_string("/sys/fs/cgroup/cpuset.cpus.effective"))
+        .unwrap_or_default().trim().to_string();
+    (online, eff)
+}
+
+/// Polls CPU hotplug state and the parent cpuset; keeps a topology restricted to usable CPUs.
+pub struct TopologyWatcher {
+    root: PathBuf,
+    online: String,
+    effective: String,
+    topo: Topology,
+    pub generation: u64,
+}
+
+impl TopologyWatcher {
+    pub fn new() -> Self {
+        Self { root: PathBuf::from(SYSFS_CPU), online: String::new(), effective: String::new(), topo: Topology::default(), generation: 0 }
+    }
+
+    pub fn topology(&self) -> &Topology { &self.topo }
+
+    pub fn allowed_cpus(&self) -> Vec<usize> { self.topo.cpus.keys().copied().collect() }
+
+    /// Re-read sysfs/cgroupfs; returns true when the usable CPU set changed.
+    pub fn refresh(&mut self, target_cg: &str) -> bool {
+        let (online, eff) = effective_cpus(&self.root, target_cg);
+        if self.generation > 0 && online == self.online && eff == self.effective { return false; }
+        let full = match Topology::from_sysfs(&self.root) {
+            Ok(t) => t,
+            Err(e) => { eprintln!("[agent] topology refresh failed: {e}"); return false; }
+        };
+        self.topo = if eff.is_empty() { full } else { full.restrict(&parse_cpu_list(&eff)) };
+        let changed = self.generation > 0;
+        if changed {
+            eprintln!("[agent] topology changed: online={} effective={} -> {} usable cpus", online, eff, self.topo.cpus.len());
+        }
+        self.online = online;
+        self.effective = eff;
+        self.generation += 1;
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> Topology {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/topology/testdata").join(name).join("cpu");
+        Topology::from_sysfs(&root).unwrap()
+    }
+
+    #[test]
+    fn cpu_list_round_trip() {
+        assert_eq!(parse_cpu_list("0-2,4,6-7\n"), vec![0, 1, 2, 4, 6, 7]);
+        assert_eq!(to_cpuset_list(&[7, 6, 4, 2, 1, 0, 1]), "0-2,4,6-7");
+        assert!(parse_cpu_list("").is_empty());
+    }
+
+    #[test]
+    fn two_socket_llc_and_nodes() {
+        let t = fixture("two_socket");
+        assert_eq!(t.cpus.len(), 8);
+        let llc: Vec<_> = t.llc_domains().into_iter().collect();
+        // L3 wins over L1d; ids fall back to the first CPU sharing the cache
+        assert_eq!(llc, vec![((0, 0), vec![0, 1, 2, 3]), ((1, 4), vec![4, 5, 6, 7])]);
+        let nodes = t.nodes();
+        assert_eq!(nodes[&0], vec![0, 1, 2, 3]);
+        assert_eq!(nodes[&1], vec![4, 5, 6, 7]);
+        assert_eq!(t.cpus[&5].package, 1);
+    }
+
+    #[test]
+    fn two_socket_smt_siblings() {
+        let t = fixture("two_socket");
+        assert_eq!(t.cpus[&2].siblings, vec![0, 2]);
+        assert_eq!(t.cpus[&7].siblings, vec![5, 7]);
+        assert!(t.cpus[&0].is_primary() && t.cpus[&5].is_primary());
+        assert!(!t.cpus[&2].is_primary() && !t.cpus[&7].is_primary());
+    }
+
+    #[test]
+    fn missing_cache_and_node_dirs() {
+        let t = fixture("no_cache");
+        // cpu1 is offline
+        assert_eq!(t.cpus.keys().copied().collect::<Vec<_>>(), vec![0, 2]);
+        assert!(t.cpus.values().all(|c| c.node == 0 && c.llc == 0 && c.capacity == 1024));
+        assert_eq!(t.llc_domains().into_iter().collect::<Vec<_>>(), vec![((0, 0), vec![0, 2])]);
+        assert_eq!(t.cpus[&2].siblings, vec![2]);
+    }
+
+    #[test]
+    fn restrict_drops_cpus() {
+        let t = fixture("two_socket").restrict(&[0, 1, 4]);
+        assert_eq!(t.nodes().into_iter().collect::<Vec<_>>(), vec![(0, vec![0, 1]), (1, vec![4])]);
+    }
+}